@@ -0,0 +1,3 @@
+-- File state recorded at scan time so rescans can skip unchanged files
+ALTER TABLE photos ADD COLUMN file_mtime INTEGER;      -- milliseconds since the Unix epoch
+ALTER TABLE photos ADD COLUMN last_scanned_at DATETIME;
@@ -0,0 +1,20 @@
+-- EXIF metadata read during scans
+CREATE TABLE photo_exif (
+    photo_id INTEGER PRIMARY KEY,
+    date_time_original TEXT,          -- local wall-clock time as recorded by the camera
+    offset_time_original TEXT,        -- e.g. +02:00, when the camera recorded it
+    camera_make TEXT,
+    camera_model TEXT,
+    lens_model TEXT,
+    iso INTEGER,
+    aperture REAL,                    -- f-number
+    exposure_time REAL,               -- seconds
+    focal_length REAL,                -- millimetres
+    orientation INTEGER,              -- EXIF orientation, 1-8
+    gps_latitude REAL,
+    gps_longitude REAL,
+    gps_altitude REAL,
+    FOREIGN KEY (photo_id) REFERENCES photos(id)
+);
+
+CREATE INDEX idx_photo_exif_camera_model ON photo_exif (camera_model);
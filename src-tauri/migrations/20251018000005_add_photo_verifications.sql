@@ -0,0 +1,8 @@
+-- When each photo's file was last checked against photos.file_hash, per drive
+CREATE TABLE photo_verifications (
+    photo_id INTEGER NOT NULL,
+    drive TEXT NOT NULL,              -- 'primary' or 'backup'
+    verified_at DATETIME NOT NULL,
+    PRIMARY KEY (photo_id, drive),
+    FOREIGN KEY (photo_id) REFERENCES photos(id)
+);
@@ -0,0 +1,12 @@
+-- Outcome of the last check of each photo's file, per drive
+ALTER TABLE photo_verifications ADD COLUMN status TEXT NOT NULL DEFAULT 'ok';  -- 'ok', 'corrupt' or 'missing'
+
+-- Progress of the integrity scrub, so a pass resumes after a restart
+CREATE TABLE scrub_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    last_photo_id INTEGER NOT NULL DEFAULT 0,  -- photos up to this id are checked; 0 when no pass is running
+    started_at DATETIME,
+    finished_at DATETIME
+);
+
+INSERT INTO scrub_state (id) VALUES (1);
@@ -0,0 +1,3 @@
+-- Perceptual hash for finding near-duplicates
+ALTER TABLE photos ADD COLUMN perceptual_hash INTEGER;     -- 64-bit dHash of the grid thumbnail
+ALTER TABLE photos ADD COLUMN perceptual_hash_of TEXT;     -- file_hash the perceptual hash was computed for
@@ -0,0 +1,8 @@
+-- Keyset pagination in filter_photos orders by one of these keys, then id
+CREATE INDEX idx_photos_sort_date_taken ON photos (COALESCE(date_taken, ''), id);
+CREATE INDEX idx_photos_sort_filename ON photos (filename, id);
+CREATE INDEX idx_photos_sort_file_size ON photos (COALESCE(file_size, 0), id);
+
+-- The primary keys start with the photo; tag and album filters start with the label
+CREATE INDEX idx_photo_tag_tag ON photo_tag (tag_id, photo_id);
+CREATE INDEX idx_photo_album_album ON photo_album (album_id, photo_id);
@@ -0,0 +1,116 @@
+use crate::error::{PhotoVaultError, Result};
+use crate::AppState;
+use log::error;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+/// Long-running work done in the background. At most one job of each kind
+/// runs at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Job {
+    Scan,
+    Scrub,
+    /// Restoring from the backup or mirroring onto it.
+    Restore,
+    Duplicates,
+}
+
+impl Job {
+    fn description(self) -> &'static str {
+        match self {
+            Job::Scan => "A library scan",
+            Job::Scrub => "An integrity scrub",
+            Job::Restore => "A restore",
+            Job::Duplicates => "A duplicate search",
+        }
+    }
+}
+
+/// Cancellation flags of the jobs in progress.
+#[derive(Default)]
+pub struct Jobs {
+    running: Mutex<HashMap<Job, Arc<AtomicBool>>>,
+}
+
+impl Jobs {
+    pub async fn is_running(&self, job: Job) -> bool {
+        self.running.lock().await.contains_key(&job)
+    }
+
+    /// Asks the running `job` to stop. Returns `false` if none was running.
+    pub async fn cancel(&self, job: Job) -> bool {
+        match self.running.lock().await.get(&job) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn start(&self, job: Job) -> Result<Arc<AtomicBool>> {
+        let mut running = self.running.lock().await;
+        if running.contains_key(&job) {
+            return Err(PhotoVaultError::conflict(format!("{} is already running", job.description())));
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        running.insert(job, cancel.clone());
+        Ok(cancel)
+    }
+
+    async fn finish(&self, job: Job) {
+        self.running.lock().await.remove(&job);
+    }
+}
+
+/// Runs `task` in the background as `job`. Its progress is reported through
+/// `<event>-progress` events and its result through `<event>-complete` or
+/// `<event>-failed`. The task runs in a task of its own, so a panic in it
+/// still ends the job and is reported as `<event>-failed`.
+pub(crate) async fn spawn_job<P, T, F, Fut>(
+    app: &AppHandle,
+    state: &AppState,
+    job: Job,
+    event: &'static str,
+    task: F,
+) -> Result<()>
+where
+    P: Serialize + 'static,
+    T: Serialize + Clone + Send + 'static,
+    F: FnOnce(Arc<AtomicBool>, Arc<dyn Fn(&P) + Send + Sync>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+{
+    let cancel = state.jobs.start(job).await?;
+    let app = app.clone();
+    tokio::spawn(async move {
+        let progress_app = app.clone();
+        let on_progress: Arc<dyn Fn(&P) + Send + Sync> = Arc::new(move |progress: &P| {
+            if let Err(e) = progress_app.emit(&format!("{}-progress", event), progress) {
+                error!("Failed to emit {} progress: {}", event, e);
+            }
+        });
+        let result = match tokio::spawn(task(cancel, on_progress)).await {
+            Ok(result) => result,
+            Err(e) => {
+                error!("{} stopped unexpectedly: {}", job.description(), e);
+                Err(PhotoVaultError::internal(format!("{} stopped unexpectedly", job.description())))
+            }
+        };
+
+        let app_state: State<AppState> = app.state();
+        app_state.jobs.finish(job).await;
+        let emitted = match result {
+            Ok(outcome) => app.emit(&format!("{}-complete", event), outcome),
+            Err(e) => app.emit(&format!("{}-failed", event), e),
+        };
+        if let Err(e) = emitted {
+            error!("Failed to emit {} result: {}", event, e);
+        }
+    });
+    Ok(())
+}
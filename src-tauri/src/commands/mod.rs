@@ -1,26 +1,197 @@
-use crate::models::{album::Album, duplicate::DuplicateGroup, filter::FilterCriteria, operation::Operation, photo::Photo, rename::{RenamePreview, RenameResult}, restore::RestoreSummary, tag::Tag};
-use crate::services::{duplicate::DuplicateDetector, filter::FilterService, rename::RenameService, restore::RestoreService, tags::TagService};
+mod jobs;
+
+pub use jobs::{Job, Jobs};
+
+use crate::models::{album::Album, drive::{DriveCheck, DriveIdentity, DriveRole, DriveUsage}, duplicate::{DuplicateCleanup, DuplicateGroup, KeeperPolicy}, exif::PhotoExif, filter::{FilterCriteria, PhotoPage}, operation::{ImportResult, Operation, PhotoOperationResult}, photo::Photo, rename::{RenamePreview, RenameResult}, restore::{DifferenceKind, RestoreChange, RestorePlan, RestoreReport, RestoreSelection}, scrub::ScrubIssue, tag::Tag, thumbnail::ThumbnailSize};
+use crate::services::{duplicate::{self, DuplicateDetector, DuplicateProgressCallback}, file_ops::{FileOperationService, ProgressCallback}, filter::FilterService, rename::RenameService, restore::{RestoreProgressCallback, RestoreService}, thumbnail::{self, ThumbnailService}};
+use crate::services::config::{load_config, save_config, AppConfig};
+use crate::services::{drive_identity, drive_space};
+use crate::services::library_path;
+use crate::services::scrub::{ScrubProgressCallback, ScrubService};
+use crate::services::sync_status::{self, SyncStatus};
+use crate::db;
+use crate::services::sync_engine::SyncEngine;
+use crate::error::{PhotoVaultError, Result};
 use crate::AppState;
-use std::path::PathBuf;
-use tauri::State;
+use jobs::spawn_job;
+use log::error;
+use std::path::{Path, PathBuf};
+use tauri::ipc::Response;
+use tauri::{AppHandle, Manager, State};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct QueueStatus {
     pub pending_operations: usize,
 }
 
+/// Starts a scan of the primary drive in the background. Progress is
+/// reported through `scan-progress` events and the result through
+/// `scan-complete` or `scan-failed`.
 #[tauri::command]
-pub async fn scan_library(primary_path: String) -> Result<Vec<Photo>, String> {
-    // Placeholder implementation
-    println!("Scanning library at: {}", primary_path);
-    Ok(vec![])
+pub async fn scan_library(app: AppHandle, state: State<'_, AppState>) -> Result<()> {
+    // Catalog paths are relative to the root scanned, so it must be the one
+    // the sync engine resolves them against.
+    let (primary_path, pool) = {
+        let sync_engine = state.sync_engine.lock().await;
+        (primary_root(&sync_engine)?, sync_engine.primary_db.clone())
+    };
+    ensure_drive(&primary_path, DriveRole::Primary)?;
+    let file_service = FileOperationService::new(primary_path, pool);
+    spawn_job(&app, &state, Job::Scan, "scan", move |cancel, on_progress: ProgressCallback| async move {
+        file_service.scan_library(cancel, on_progress).await
+    })
+    .await
 }
 
+/// Asks the running scan to stop. Returns `false` if no scan was running.
 #[tauri::command]
-pub async fn get_photos(limit: i64, offset: i64) -> Result<Vec<Photo>, String> {
-    // Placeholder implementation
-    println!("Getting photos with limit: {} and offset: {}", limit, offset);
-    Ok(vec![])
+pub async fn cancel_scan(state: State<'_, AppState>) -> Result<bool> {
+    Ok(state.jobs.cancel(Job::Scan).await)
+}
+
+/// Starts an integrity scrub in the background, resuming a pass that was
+/// stopped. Progress is reported through `scrub-progress` events and the
+/// result through `scrub-complete` or `scrub-failed`.
+#[tauri::command]
+pub async fn start_scrub(app: AppHandle, state: State<'_, AppState>) -> Result<()> {
+    spawn_scrub(&app, &state).await
+}
+
+pub(crate) async fn spawn_scrub(app: &AppHandle, state: &AppState) -> Result<()> {
+    let scrub = {
+        let sync_engine = state.sync_engine.lock().await;
+        scrub_service(&sync_engine)?
+    };
+    spawn_job(app, state, Job::Scrub, "scrub", move |cancel, on_progress: ScrubProgressCallback| async move {
+        scrub.run(cancel, on_progress).await
+    })
+    .await
+}
+
+/// Asks the running scrub to stop. Returns `false` if no scrub was running.
+#[tauri::command]
+pub async fn cancel_scrub(state: State<'_, AppState>) -> Result<bool> {
+    Ok(state.jobs.cancel(Job::Scrub).await)
+}
+
+/// Files that failed their last integrity check, on either drive.
+#[tauri::command]
+pub async fn get_scrub_issues(state: State<'_, AppState>) -> Result<Vec<ScrubIssue>> {
+    let pool = state.sync_engine.lock().await.primary_db.clone();
+    ScrubService::issues(&pool).await
+}
+
+/// Replaces the bad copy of a photo with the good copy from the other drive.
+#[tauri::command]
+pub async fn repair_photo_copy(photo_id: i64, state: State<'_, AppState>) -> Result<()> {
+    // Held throughout, so no operation moves the file while it is repaired.
+    let sync_engine = state.sync_engine.lock().await;
+    scrub_service(&sync_engine)?.repair(photo_id).await
+}
+
+/// The backup is only scrubbed while it is attached and has caught up with
+/// the primary.
+fn scrub_service(sync_engine: &SyncEngine) -> Result<ScrubService> {
+    let backup_root = sync_engine
+        .backup_root
+        .clone()
+        .filter(|_| sync_engine.backup_db.is_some() && sync_engine.operation_queue.is_empty());
+    Ok(ScrubService::new(
+        sync_engine.primary_db.clone(),
+        primary_root(sync_engine)?,
+        backup_root,
+    ))
+}
+
+#[tauri::command]
+pub async fn get_photos(limit: i64, offset: i64, state: State<'_, AppState>) -> Result<Vec<Photo>> {
+    let pool = state.sync_engine.lock().await.primary_db.clone();
+    let photos = sqlx::query_as::<_, Photo>("SELECT * FROM photos ORDER BY id LIMIT ? OFFSET ?")
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&pool)
+        .await?;
+    Ok(photos)
+}
+
+#[tauri::command]
+pub async fn get_photo_exif(photo_id: i64, state: State<'_, AppState>) -> Result<Option<PhotoExif>> {
+    let pool = state.sync_engine.lock().await.primary_db.clone();
+    let exif = sqlx::query_as::<_, PhotoExif>("SELECT * FROM photo_exif WHERE photo_id = ?")
+        .bind(photo_id)
+        .fetch_optional(&pool)
+        .await?;
+    Ok(exif)
+}
+
+/// Returns the JPEG bytes of a cached thumbnail, generating it on first use.
+#[tauri::command]
+pub async fn get_thumbnail(
+    photo_id: i64,
+    size: ThumbnailSize,
+    state: State<'_, AppState>,
+) -> Result<Response> {
+    let (pool, primary_root) = {
+        let sync_engine = state.sync_engine.lock().await;
+        (sync_engine.primary_db.clone(), primary_root(&sync_engine)?)
+    };
+    let (path, file_hash): (String, String) =
+        sqlx::query_as("SELECT path, file_hash FROM photos WHERE id = ?")
+            .bind(photo_id)
+            .fetch_optional(&pool)
+            .await?
+            .ok_or_else(|| PhotoVaultError::not_found(format!("Photo {}", photo_id)))?;
+
+    let thumbnail_service = ThumbnailService::new(&primary_root);
+    let bytes = thumbnail_service
+        .generate_thumbnail(&library_path::resolve(&primary_root, &path)?, &file_hash, size)
+        .await?;
+    Ok(Response::new(bytes))
+}
+
+/// Number of photos no thumbnail can be made of, such as HEIC photos.
+#[tauri::command]
+pub async fn count_photos_without_thumbnails(state: State<'_, AppState>) -> Result<u64> {
+    let pool = state.sync_engine.lock().await.primary_db.clone();
+    thumbnail::count_unsupported(&pool).await
+}
+
+#[tauri::command]
+pub async fn clear_thumbnail_cache() -> Result<()> {
+    let config = load_config()?;
+    for root in config.primary_path.iter().chain(config.backup_path.iter()) {
+        ThumbnailService::new(root).clear_cache().await?;
+    }
+    Ok(())
+}
+
+fn primary_root(sync_engine: &SyncEngine) -> Result<PathBuf> {
+    sync_engine
+        .primary_root
+        .clone()
+        .ok_or(PhotoVaultError::DriveOffline { drive: DriveRole::Primary })
+}
+
+/// Looks up a photo in the primary catalog and checks that its file exists.
+async fn find_photo(sync_engine: &SyncEngine, photo_id: i64) -> Result<Photo> {
+    let photo = sqlx::query_as::<_, Photo>("SELECT * FROM photos WHERE id = ?")
+        .bind(photo_id)
+        .fetch_optional(&sync_engine.primary_db)
+        .await?
+        .ok_or_else(|| PhotoVaultError::not_found(format!("Photo {}", photo_id)))?;
+    if !library_path::resolve(&primary_root(sync_engine)?, &photo.path)?.exists() {
+        return Err(PhotoVaultError::not_found(photo.path));
+    }
+    Ok(photo)
+}
+
+fn operation_result(photo_id: i64, result: Result<()>) -> PhotoOperationResult {
+    PhotoOperationResult {
+        photo_id,
+        success: result.is_ok(),
+        error: result.err(),
+    }
 }
 
 #[tauri::command]
@@ -28,22 +199,47 @@ pub async fn move_photos(
     photo_ids: Vec<i64>,
     target_path: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    // This is a placeholder. In a real app, you would look up the photo paths from the database.
-    let operation = Operation::Move {
-        from: PathBuf::from("..."),
-        to: PathBuf::from(target_path),
-    };
-    state.sync_engine.lock().await.execute_operation(operation).await
+) -> Result<Vec<PhotoOperationResult>> {
+    let mut sync_engine = state.sync_engine.lock().await;
+    // `target_path` is a folder relative to the library root.
+    let target_dir = library_path::to_catalog_path(Path::new(""), Path::new(&target_path))
+        .ok_or_else(|| PhotoVaultError::invalid_input(format!("{} is not a folder in the library", target_path)))?;
+
+    let mut results = Vec::with_capacity(photo_ids.len());
+    for photo_id in photo_ids {
+        let result = match find_photo(&sync_engine, photo_id).await {
+            Ok(photo) => {
+                let operation = Operation::Move {
+                    to: library_path::join(&target_dir, &photo.filename),
+                    from: photo.path,
+                };
+                sync_engine.execute_operation(operation).await
+            }
+            Err(e) => Err(e),
+        };
+        results.push(operation_result(photo_id, result));
+    }
+    Ok(results)
 }
 
 #[tauri::command]
-pub async fn delete_photos(photo_ids: Vec<i64>, state: State<'_, AppState>) -> Result<(), String> {
-    // This is a placeholder. In a real app, you would look up the photo paths from the database.
-    let operation = Operation::Delete {
-        path: PathBuf::from("..."),
-    };
-    state.sync_engine.lock().await.execute_operation(operation).await
+pub async fn delete_photos(
+    photo_ids: Vec<i64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<PhotoOperationResult>> {
+    let mut sync_engine = state.sync_engine.lock().await;
+    let mut results = Vec::with_capacity(photo_ids.len());
+    for photo_id in photo_ids {
+        let result = match find_photo(&sync_engine, photo_id).await {
+            Ok(photo) => {
+                let operation = Operation::Delete { path: photo.path };
+                sync_engine.execute_operation(operation).await
+            }
+            Err(e) => Err(e),
+        };
+        results.push(operation_result(photo_id, result));
+    }
+    Ok(results)
 }
 
 #[tauri::command]
@@ -51,17 +247,54 @@ pub async fn rename_photo(
     photo_id: i64,
     new_name: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    // This is a placeholder. In a real app, you would look up the photo path from the database.
+) -> Result<()> {
+    let mut sync_engine = state.sync_engine.lock().await;
+    let photo = find_photo(&sync_engine, photo_id).await?;
     let operation = Operation::Rename {
-        path: PathBuf::from("..."),
+        path: photo.path,
         new_name,
     };
-    state.sync_engine.lock().await.execute_operation(operation).await
+    sync_engine.execute_operation(operation).await
 }
 
+/// Copies files from outside the library into `target_folder`, a folder
+/// relative to the library root, on both drives.
 #[tauri::command]
-pub async fn get_sync_queue_status(state: State<'_, AppState>) -> Result<QueueStatus, String> {
+pub async fn import_photos(
+    source_paths: Vec<String>,
+    target_folder: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<ImportResult>> {
+    let target_dir = library_path::to_catalog_path(Path::new(""), Path::new(&target_folder))
+        .ok_or_else(|| PhotoVaultError::invalid_input(format!("{} is not a folder in the library", target_folder)))?;
+    let mut sync_engine = state.sync_engine.lock().await;
+    let mut results = Vec::with_capacity(source_paths.len());
+    for source in source_paths {
+        let file_name = Path::new(&source)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        let result = match file_name {
+            Some(file_name) => {
+                let path = library_path::join(&target_dir, &file_name);
+                let operation = Operation::Import {
+                    source: source.clone(),
+                    path: path.clone(),
+                };
+                sync_engine.execute_operation(operation).await.map(|()| path)
+            }
+            None => Err(PhotoVaultError::invalid_input(format!("{} is not a file", source))),
+        };
+        results.push(ImportResult {
+            source,
+            path: result.as_ref().ok().cloned(),
+            error: result.err(),
+        });
+    }
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn get_sync_queue_status(state: State<'_, AppState>) -> Result<QueueStatus> {
     let pending_operations = state.sync_engine.lock().await.operation_queue.len();
     Ok(QueueStatus {
         pending_operations,
@@ -69,7 +302,116 @@ pub async fn get_sync_queue_status(state: State<'_, AppState>) -> Result<QueueSt
 }
 
 #[tauri::command]
-pub async fn create_album(name: String, state: State<'_, AppState>) -> Result<(), String> {
+pub async fn verify_sync_status(state: State<'_, AppState>) -> Result<SyncStatus> {
+    let sync_engine = state.sync_engine.lock().await;
+    sync_status::verify_sync_status(&sync_engine).await
+}
+
+/// Capacity, free space and library size of the primary and backup drives.
+#[tauri::command]
+pub async fn get_drive_usage(state: State<'_, AppState>) -> Result<Vec<DriveUsage>> {
+    let sync_engine = state.sync_engine.lock().await;
+    let primary = drive_space::drive_usage(
+        DriveRole::Primary,
+        sync_engine.primary_root.as_deref(),
+        Some(&sync_engine.primary_db),
+    )
+    .await?;
+    let backup = drive_space::drive_usage(
+        DriveRole::Backup,
+        sync_engine.backup_root.as_deref(),
+        sync_engine.backup_db.as_ref(),
+    )
+    .await?;
+    Ok(vec![primary, backup])
+}
+
+/// Fails unless the drive at `path` carries this library's identity in `role`.
+fn ensure_drive(path: &Path, role: DriveRole) -> Result<()> {
+    let config = load_config()?;
+    drive_identity::ensure_drive(path, config.library_id, role)
+}
+
+/// Reports whether the drive at `path` can be used in `role` for this library.
+#[tauri::command]
+pub async fn inspect_drive(path: String, role: DriveRole) -> Result<DriveCheck> {
+    let config = load_config()?;
+    drive_identity::check_drive(Path::new(&path), config.library_id, role)
+}
+
+/// Writes a new identity to a blank drive and makes it the library's drive in
+/// `role`. Initializing the first drive creates the library.
+#[tauri::command]
+pub async fn initialize_drive(
+    path: String,
+    role: DriveRole,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<DriveIdentity> {
+    let mut config = load_config()?;
+    let library_id = config.library_id.unwrap_or_else(Uuid::new_v4);
+    let identity = drive_identity::initialize_drive(Path::new(&path), library_id, role)?;
+    config.library_id = Some(library_id);
+    use_drive(&mut config, PathBuf::from(path), role, &app, &state).await?;
+    Ok(identity)
+}
+
+/// Takes over a drive that already carries an identity. With no library set
+/// up yet, the drive's library becomes this machine's library; otherwise the
+/// drive is rewritten to belong to the current library.
+#[tauri::command]
+pub async fn adopt_drive(
+    path: String,
+    role: DriveRole,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<DriveIdentity> {
+    let mut config = load_config()?;
+    let root = Path::new(&path);
+    let library_id = match config.library_id {
+        Some(library_id) => library_id,
+        None => drive_identity::read_identity(root)?
+            .map(|identity| identity.library_id)
+            .unwrap_or_else(Uuid::new_v4),
+    };
+    let identity = drive_identity::adopt_drive(root, library_id, role)?;
+    config.library_id = Some(library_id);
+    use_drive(&mut config, PathBuf::from(path), role, &app, &state).await?;
+    Ok(identity)
+}
+
+/// Records `path` as the library's drive in `role`. A new primary drive
+/// brings its own catalog, so the sync engine is rebuilt around it.
+async fn use_drive(
+    config: &mut AppConfig,
+    path: PathBuf,
+    role: DriveRole,
+    app: &AppHandle,
+    state: &State<'_, AppState>,
+) -> Result<()> {
+    match role {
+        DriveRole::Primary => {
+            let migrations_path = app
+                .path()
+                .resolve("migrations", tauri::path::BaseDirectory::Resource)
+                .map_err(|e| PhotoVaultError::internal(e.to_string()))?;
+            let pool = db::open_catalog(&path, &migrations_path).await?;
+            library_path::migrate_absolute_paths(&pool, &path).await?;
+            let mut sync_engine = state.sync_engine.lock().await;
+            sync_engine.detach_backup().await;
+            // The drive monitor reattaches the backup on its next poll.
+            *sync_engine = SyncEngine::new(pool, None, Some(path.clone()), config.backup_path.clone());
+            crate::restore_queue(&mut sync_engine).await;
+            config.primary_path = Some(path);
+        }
+        // The drive monitor attaches the backup on its next poll.
+        DriveRole::Backup => config.backup_path = Some(path),
+    }
+    save_config(config)
+}
+
+#[tauri::command]
+pub async fn create_album(name: String, state: State<'_, AppState>) -> Result<()> {
     let operation = Operation::CreateAlbum { name };
     state.sync_engine.lock().await.execute_operation(operation).await
 }
@@ -79,55 +421,59 @@ pub async fn add_photos_to_album(
     photo_ids: Vec<i64>,
     album_id: i64,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<()> {
+    let mut sync_engine = state.sync_engine.lock().await;
+    let album: String = sqlx::query_scalar("SELECT name FROM albums WHERE id = ?")
+        .bind(album_id)
+        .fetch_optional(&sync_engine.primary_db)
+        .await?
+        .ok_or_else(|| PhotoVaultError::not_found(format!("Album {}", album_id)))?;
     for photo_id in photo_ids {
-        let operation = Operation::AddToAlbum { photo_id, album_id };
-        state.sync_engine.lock().await.execute_operation(operation).await?;
+        let photo = find_photo(&sync_engine, photo_id).await?;
+        let operation = Operation::AddToAlbum { path: photo.path, album: album.clone() };
+        sync_engine.execute_operation(operation).await?;
     }
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_albums(state: State<'_, AppState>) -> Result<Vec<Album>, String> {
+pub async fn get_albums(state: State<'_, AppState>) -> Result<Vec<Album>> {
     let pool = state.sync_engine.lock().await.primary_db.clone();
     let albums = sqlx::query_as::<_, Album>("SELECT * FROM albums")
         .fetch_all(&pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(albums)
 }
 
 #[tauri::command]
-pub async fn delete_album(album_id: i64, state: State<'_, AppState>) -> Result<(), String> {
+pub async fn delete_album(album_id: i64, state: State<'_, AppState>) -> Result<()> {
     // This should also be an operation, but for simplicity, we'll just delete it directly.
     let pool = state.sync_engine.lock().await.primary_db.clone();
     sqlx::query("DELETE FROM photo_album WHERE album_id = ?")
         .bind(album_id)
         .execute(&pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     sqlx::query("DELETE FROM albums WHERE id = ?")
         .bind(album_id)
         .execute(&pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn add_tag(photo_id: i64, tag_name: String, state: State<'_, AppState>) -> Result<(), String> {
-    let pool = state.sync_engine.lock().await.primary_db.clone();
-    let tag_service = TagService::new(pool);
-    tag_service.add_tag(photo_id, tag_name).await.map_err(|e| e.to_string())
+pub async fn add_tag(photo_id: i64, tag_name: String, state: State<'_, AppState>) -> Result<()> {
+    let mut sync_engine = state.sync_engine.lock().await;
+    let photo = find_photo(&sync_engine, photo_id).await?;
+    let operation = Operation::AddTag { path: photo.path, tag_name };
+    sync_engine.execute_operation(operation).await
 }
 
 #[tauri::command]
-pub async fn get_all_tags(state: State<'_, AppState>) -> Result<Vec<Tag>, String> {
+pub async fn get_all_tags(state: State<'_, AppState>) -> Result<Vec<Tag>> {
     let pool = state.sync_engine.lock().await.primary_db.clone();
     let tags = sqlx::query_as::<_, Tag>("SELECT * FROM tags")
         .fetch_all(&pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(tags)
 }
 
@@ -135,39 +481,75 @@ pub async fn get_all_tags(state: State<'_, AppState>) -> Result<Vec<Tag>, String
 pub async fn filter_photos(
     criteria: FilterCriteria,
     state: State<'_, AppState>,
-) -> Result<Vec<Photo>, String> {
+) -> Result<PhotoPage> {
     let pool = state.sync_engine.lock().await.primary_db.clone();
     let filter_service = FilterService::new(pool);
-    filter_service.filter_photos(criteria).await.map_err(|e| e.to_string())
+    filter_service.filter_photos(criteria).await
 }
 
 #[tauri::command]
-pub async fn search_photos(query: String, state: State<'_, AppState>) -> Result<Vec<Photo>, String> {
+pub async fn search_photos(
+    query: String,
+    after: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<PhotoPage> {
     let pool = state.sync_engine.lock().await.primary_db.clone();
     let criteria = FilterCriteria {
         query: Some(query),
+        after,
         ..Default::default()
     };
     let filter_service = FilterService::new(pool);
-    filter_service.filter_photos(criteria).await.map_err(|e| e.to_string())
+    filter_service.filter_photos(criteria).await
+}
+
+/// Starts looking for duplicate photos in the background: identical files,
+/// and copies whose perceptual similarity is at least `threshold` (0 to 1).
+/// Progress is reported through `duplicates-progress` events and the result
+/// through `duplicates-complete` or `duplicates-failed`.
+#[tauri::command]
+pub async fn find_duplicates(threshold: f32, app: AppHandle, state: State<'_, AppState>) -> Result<()> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(PhotoVaultError::invalid_input(format!("Similarity threshold {} is not between 0 and 1", threshold)));
+    }
+    let detector = {
+        let sync_engine = state.sync_engine.lock().await;
+        DuplicateDetector::new(sync_engine.primary_db.clone(), primary_root(&sync_engine)?)
+    };
+    spawn_job(&app, &state, Job::Duplicates, "duplicates", move |cancel, on_progress: DuplicateProgressCallback| {
+        async move { detector.find_duplicates(threshold, cancel, on_progress).await }
+    })
+    .await
 }
 
+/// Asks the running duplicate search to stop. Returns `false` if none was
+/// running.
 #[tauri::command]
-pub async fn find_duplicates() -> Result<Vec<DuplicateGroup>, String> {
-    DuplicateDetector::find_duplicates(0.9).await
+pub async fn cancel_find_duplicates(state: State<'_, AppState>) -> Result<bool> {
+    Ok(state.jobs.cancel(Job::Duplicates).await)
 }
 
+/// Keeps one photo of each group, chosen by `policy`, and deletes the others
+/// on both drives. The result counts the bytes actually freed.
 #[tauri::command]
-pub async fn delete_duplicates(photo_ids: Vec<i64>) -> Result<i64, String> {
-    // returns space freed
-    Ok(0)
+pub async fn delete_duplicates(
+    groups: Vec<DuplicateGroup>,
+    policy: KeeperPolicy,
+    state: State<'_, AppState>,
+) -> Result<DuplicateCleanup> {
+    let groups: Vec<Vec<i64>> = groups
+        .iter()
+        .map(|group| group.photos.iter().map(|photo| photo.id).collect())
+        .collect();
+    let mut sync_engine = state.sync_engine.lock().await;
+    duplicate::delete_duplicates(&mut sync_engine, &groups, &policy).await
 }
 
 #[tauri::command]
 pub async fn bulk_rename(
     photo_ids: Vec<i64>,
     pattern: String,
-) -> Result<Vec<RenameResult>, String> {
+) -> Result<Vec<RenameResult>> {
     RenameService::bulk_rename(photo_ids, pattern).await
 }
 
@@ -175,16 +557,126 @@ pub async fn bulk_rename(
 pub async fn preview_bulk_rename(
     photo_ids: Vec<i64>,
     pattern: String,
-) -> Result<Vec<RenamePreview>, String> {
+) -> Result<Vec<RenamePreview>> {
     RenameService::preview_bulk_rename(photo_ids, pattern).await
 }
 
+/// Compares the drives and returns one page of the files that differ,
+/// optionally only those of `kind`.
+#[tauri::command]
+pub async fn detect_backup_differences(
+    kind: Option<DifferenceKind>,
+    limit: usize,
+    offset: usize,
+    state: State<'_, AppState>,
+) -> Result<RestoreReport> {
+    let service = {
+        let sync_engine = state.sync_engine.lock().await;
+        restore_service(&sync_engine)?
+    };
+    service.detect_differences(kind, limit, offset).await
+}
+
+/// Lists what restoring `selection` from the backup would change on the
+/// primary, without changing anything.
+#[tauri::command]
+pub async fn preview_restore(selection: RestoreSelection, state: State<'_, AppState>) -> Result<RestorePlan> {
+    let service = {
+        let sync_engine = state.sync_engine.lock().await;
+        restore_service(&sync_engine)?
+    };
+    service.plan(&selection).await
+}
+
+/// Starts restoring `selection` from the backup in the background. Progress
+/// is reported through `restore-progress` events and the result through
+/// `restore-complete` or `restore-failed`.
+#[tauri::command]
+pub async fn restore_backup_to_primary(
+    selection: RestoreSelection,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<()> {
+    let (service, suspension) = {
+        let sync_engine = state.sync_engine.lock().await;
+        // Otherwise the backup would bring back files the primary has since
+        // moved or deleted.
+        if !sync_engine.operation_queue.is_empty() {
+            return Err(PhotoVaultError::conflict("The backup has not caught up with the primary yet"));
+        }
+        (restore_service(&sync_engine)?, sync_engine.suspend()?)
+    };
+    spawn_job(&app, &state, Job::Restore, "restore", move |cancel, on_progress: RestoreProgressCallback| {
+        async move {
+            // No other change may touch the drives until the restore is done.
+            let _suspension = suspension;
+            service.restore(&selection, cancel, on_progress).await
+        }
+    })
+    .await
+}
+
+/// Lists what mirroring the primary onto the backup would change, without
+/// changing anything.
+#[tauri::command]
+pub async fn preview_mirror_to_backup(state: State<'_, AppState>) -> Result<Vec<RestoreChange>> {
+    let service = {
+        let sync_engine = state.sync_engine.lock().await;
+        restore_service(&sync_engine)?
+    };
+    service.plan_mirror().await
+}
+
+/// Starts making the backup an exact copy of the primary in the background.
+/// Files only the backup has are quarantined on it. Progress is reported
+/// through `mirror-progress` events and the result through `mirror-complete`
+/// or `mirror-failed`.
 #[tauri::command]
-pub async fn detect_backup_differences() -> Result<RestoreSummary, String> {
-    RestoreService::detect_differences().await
+pub async fn mirror_primary_to_backup(app: AppHandle, state: State<'_, AppState>) -> Result<()> {
+    let (service, queued, suspension) = {
+        let sync_engine = state.sync_engine.lock().await;
+        let queued: Vec<String> = sync_engine.operation_queue.iter().map(|queued| queued.id.clone()).collect();
+        (restore_service(&sync_engine)?, queued, sync_engine.suspend()?)
+    };
+    let settle_app = app.clone();
+    spawn_job(&app, &state, Job::Restore, "mirror", move |cancel, on_progress: RestoreProgressCallback| {
+        async move {
+            // The backup catalog is overwritten at the end, which would drop
+            // the rows of any operation applied meanwhile.
+            let _suspension = suspension;
+            let result = service.mirror(cancel, on_progress).await;
+            // Operations the backup owed from before the mirror are covered
+            // by it.
+            if let Ok(outcome) = &result {
+                if !outcome.cancelled && outcome.failed.is_empty() {
+                    let app_state: State<AppState> = settle_app.state();
+                    let mut sync_engine = app_state.sync_engine.lock().await;
+                    if let Err(e) = sync_engine.settle_queued(&queued).await {
+                        error!("Failed to settle queued operations: {}", e);
+                    }
+                }
+            }
+            result
+        }
+    })
+    .await
 }
 
+/// Asks the running restore to stop. Returns `false` if no restore was
+/// running.
 #[tauri::command]
-pub async fn restore_backup_to_primary() -> Result<(), String> {
-    RestoreService::restore_backup_to_primary().await
+pub async fn cancel_restore(state: State<'_, AppState>) -> Result<bool> {
+    Ok(state.jobs.cancel(Job::Restore).await)
+}
+
+fn restore_service(sync_engine: &SyncEngine) -> Result<RestoreService> {
+    let (Some(backup_db), Some(backup_root)) = (&sync_engine.backup_db, &sync_engine.backup_root) else {
+        return Err(PhotoVaultError::DriveOffline { drive: DriveRole::Backup });
+    };
+    Ok(RestoreService::new(
+        sync_engine.primary_db.clone(),
+        primary_root(sync_engine)?,
+        backup_db.clone(),
+        backup_root.clone(),
+    ))
 }
@@ -1,6 +1,7 @@
 use sqlx::SqlitePool;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use crate::db;
+use log::warn;
 
 pub struct DatabaseManager {
     pub primary_db: SqlitePool,
@@ -8,12 +9,20 @@ pub struct DatabaseManager {
 }
 
 impl DatabaseManager {
-    pub async fn initialize(primary_path: PathBuf, backup_path: Option<PathBuf>, migrations_path: &Path) -> Result<Self, sqlx::Error> {
-        let primary_db = db::init_db(&primary_path, migrations_path).await?;
-        let backup_db = if let Some(backup_path) = backup_path {
-            Some(db::init_db(&backup_path, migrations_path).await?)
-        } else {
-            None
+    /// Opens the catalogs kept under `.photovault/` on each drive.
+    pub async fn initialize(primary_root: &Path, backup_root: Option<&Path>, migrations_path: &Path) -> Result<Self, sqlx::Error> {
+        let primary_db = db::open_catalog(primary_root, migrations_path).await?;
+        // A backup catalog that cannot be opened is treated like an unplugged
+        // backup drive rather than keeping the primary from opening.
+        let backup_db = match backup_root {
+            Some(backup_root) => match db::open_catalog(backup_root, migrations_path).await {
+                Ok(pool) => Some(pool),
+                Err(e) => {
+                    warn!("Failed to open backup catalog on {}: {}", backup_root.display(), e);
+                    None
+                }
+            },
+            None => None,
         };
 
         Ok(DatabaseManager {
@@ -1,9 +1,101 @@
 use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
-use std::path::Path;
-use log::error;
+use std::path::{Path, PathBuf};
+use log::{error, info};
 
 pub mod manager;
 
+/// Location of the catalog database on a library drive.
+pub fn catalog_path(drive_root: &Path) -> PathBuf {
+    drive_root.join(".photovault").join("photovault.db")
+}
+
+/// Opens (creating if needed) the catalog stored on the drive at `drive_root`.
+pub async fn open_catalog(drive_root: &Path, migrations_path: &Path) -> Result<SqlitePool, sqlx::Error> {
+    let catalog = catalog_path(drive_root);
+    if let Some(parent) = catalog.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    init_db(&catalog, migrations_path).await
+}
+
+/// Moves a catalog from the old app-data location onto the primary drive.
+/// Does nothing when the drive already has a catalog or there is no old one.
+pub async fn import_legacy_catalog(legacy_path: &Path, drive_root: &Path) -> Result<bool, sqlx::Error> {
+    let catalog = catalog_path(drive_root);
+    if catalog.exists() || !legacy_path.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = catalog.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // VACUUM INTO writes a consistent copy even if the old database still
+    // has a write-ahead log next to it.
+    let legacy = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(sqlx::sqlite::SqliteConnectOptions::new().filename(legacy_path))
+        .await?;
+    sqlx::query("VACUUM INTO ?")
+        .bind(catalog.to_string_lossy().into_owned())
+        .execute(&legacy)
+        .await?;
+    legacy.close().await;
+
+    tokio::fs::rename(legacy_path, legacy_path.with_extension("db.migrated")).await?;
+    info!("Moved catalog from {} to {}", legacy_path.display(), catalog.display());
+    Ok(true)
+}
+
+/// Tables that describe the library itself, children first. The sync
+/// journal and integrity records are the primary catalog's own.
+const LIBRARY_TABLES: [&str; 6] = ["photo_exif", "photo_album", "photo_tag", "photos", "albums", "tags"];
+
+/// Replaces the library tables of `target` with those of `source`, keeping
+/// row ids, in one transaction. Photos at `excluded_paths` are left out,
+/// along with their EXIF, album and tag rows. Both catalogs must be migrated
+/// to the same schema.
+pub async fn copy_library_tables(
+    source: &SqlitePool,
+    target: &SqlitePool,
+    excluded_paths: &[String],
+) -> Result<(), sqlx::Error> {
+    let source_file: String = sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+        .fetch_one(source)
+        .await?;
+    let mut conn = target.acquire().await?;
+    sqlx::query("ATTACH DATABASE ? AS source")
+        .bind(&source_file)
+        .execute(&mut *conn)
+        .await?;
+    let copied = async {
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        for table in LIBRARY_TABLES {
+            sqlx::query(&format!("DELETE FROM main.{}", table)).execute(&mut *tx).await?;
+        }
+        for table in LIBRARY_TABLES.iter().rev() {
+            sqlx::query(&format!("INSERT INTO main.{0} SELECT * FROM source.{0}", table))
+                .execute(&mut *tx)
+                .await?;
+        }
+        for path in excluded_paths {
+            for table in ["photo_exif", "photo_album", "photo_tag"] {
+                sqlx::query(&format!(
+                    "DELETE FROM main.{} WHERE photo_id IN (SELECT id FROM main.photos WHERE path = ?)",
+                    table
+                ))
+                .bind(path)
+                .execute(&mut *tx)
+                .await?;
+            }
+            sqlx::query("DELETE FROM main.photos WHERE path = ?").bind(path).execute(&mut *tx).await?;
+        }
+        tx.commit().await
+    }
+    .await;
+    sqlx::query("DETACH DATABASE source").execute(&mut *conn).await?;
+    copied
+}
+
 pub async fn init_db(db_path: &Path, migrations_path: &Path) -> Result<SqlitePool, sqlx::Error> {
     let pool = SqlitePoolOptions::new()
         .max_connections(5)
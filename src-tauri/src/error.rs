@@ -0,0 +1,117 @@
+use crate::models::drive::DriveRole;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Errors returned by services and commands. Serialized with a `kind` tag so
+/// the frontend can tell the cases apart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum PhotoVaultError {
+    /// A drive the operation needs is not mounted or not configured.
+    #[error("The {drive:?} drive is offline")]
+    DriveOffline { drive: DriveRole },
+    /// A mounted drive does not carry this library's identity.
+    #[error("{path} cannot be used: {reason}")]
+    DriveMismatch { path: String, reason: String },
+    #[error("Not enough space on the {drive:?} drive at {path}: {required} bytes needed, {available} available")]
+    InsufficientSpace {
+        drive: DriveRole,
+        path: String,
+        required: u64,
+        available: u64,
+    },
+    /// A file's contents do not match the hash recorded in the catalog.
+    #[error("{path} on the {drive:?} drive does not match its recorded hash")]
+    ChecksumMismatch { drive: DriveRole, path: String },
+    #[error("{what} not found")]
+    NotFound { what: String },
+    /// The change clashes with existing state, e.g. a file already exists.
+    #[error("{message}")]
+    Conflict { message: String },
+    #[error("{message}")]
+    InvalidInput { message: String },
+    #[error("{message}")]
+    UnsupportedFormat { message: String },
+    #[error("I/O error{}: {message}", path.as_ref().map(|p| format!(" on {}", p)).unwrap_or_default())]
+    Io { path: Option<String>, message: String },
+    #[error("Database error: {message}")]
+    Database { message: String },
+    #[error("{message}")]
+    Internal { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, PhotoVaultError>;
+
+impl PhotoVaultError {
+    pub fn not_found(what: impl Into<String>) -> Self {
+        Self::NotFound { what: what.into() }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict { message: message.into() }
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::InvalidInput { message: message.into() }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::Internal { message: message.into() }
+    }
+
+    /// An I/O error on `path`. `NotFound` errors become `NotFound` so the
+    /// frontend does not have to look inside I/O errors for them.
+    pub fn io(path: &Path, error: std::io::Error) -> Self {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            return Self::not_found(path.display().to_string());
+        }
+        Self::Io {
+            path: Some(path.display().to_string()),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for PhotoVaultError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io {
+            path: None,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<sqlx::Error> for PhotoVaultError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => Self::not_found("Row"),
+            error => Self::Database {
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+impl From<serde_json::Error> for PhotoVaultError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::internal(error.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for PhotoVaultError {
+    fn from(error: tokio::task::JoinError) -> Self {
+        Self::internal(error.to_string())
+    }
+}
+
+impl From<image::ImageError> for PhotoVaultError {
+    fn from(error: image::ImageError) -> Self {
+        match error {
+            image::ImageError::IoError(error) => error.into(),
+            image::ImageError::Unsupported(error) => Self::UnsupportedFormat {
+                message: error.to_string(),
+            },
+            error => Self::internal(error.to_string()),
+        }
+    }
+}
@@ -1,15 +1,74 @@
 mod commands;
 mod db;
+mod error;
 mod models;
 mod services;
 
+use commands::{Job, Jobs};
+use log::{error, info};
+use models::drive::DriveRole;
+use services::{drive_identity, library_path};
+use services::drive_monitor::{DriveMonitor, DRIVE_POLL_INTERVAL};
+use services::scrub::{ScrubService, SCRUB_CHECK_INTERVAL};
 use services::sync_engine::SyncEngine;
 use sqlx::SqlitePool;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::Mutex;
 
 pub struct AppState {
     pub sync_engine: Mutex<SyncEngine>,
+    /// Background jobs in progress.
+    pub jobs: Jobs,
+}
+
+/// Settles operations interrupted by a crash, reloads the ones the backup
+/// still owes from the sync journal and replays them if the backup is
+/// attached.
+pub(crate) async fn restore_queue(sync_engine: &mut SyncEngine) {
+    match sync_engine.recover_interrupted_operations().await {
+        Ok(count) if count > 0 => info!("Recovered {} interrupted operations", count),
+        Ok(_) => {}
+        Err(e) => error!("Failed to recover interrupted operations: {}", e),
+    }
+    match sync_engine.load_pending_operations().await {
+        Ok(count) if count > 0 => {
+            info!("Restored {} queued operations from the sync journal", count);
+            if let Err(e) = sync_engine.flush_queue().await {
+                error!("Failed to replay queued operations: {}", e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => error!("Failed to load queued operations: {}", e),
+    }
+}
+
+/// Starts an integrity scrub whenever one is due, which also resumes a pass
+/// that was stopped when the app last quit.
+async fn scrub_when_due(handle: AppHandle) {
+    let mut interval = tokio::time::interval(SCRUB_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let app_state: tauri::State<AppState> = handle.state();
+        let pool = {
+            let sync_engine = app_state.sync_engine.lock().await;
+            if sync_engine.primary_root.is_none() {
+                continue;
+            }
+            sync_engine.primary_db.clone()
+        };
+        if app_state.jobs.is_running(Job::Scrub).await {
+            continue;
+        }
+        match ScrubService::is_due(&pool).await {
+            Ok(true) => {
+                if let Err(e) = commands::spawn_scrub(&handle, &app_state).await {
+                    error!("Failed to start the integrity scrub: {}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => error!("Failed to check whether a scrub is due: {}", e),
+        }
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -19,15 +78,39 @@ pub fn run() {
             sync_engine: Mutex::new(SyncEngine::new(
                 SqlitePool::connect_lazy("sqlite::memory:").unwrap(),
                 None,
+                None,
+                None,
             )),
+            jobs: Jobs::default(),
         })
         .invoke_handler(tauri::generate_handler![
             commands::scan_library,
+            commands::cancel_scan,
+            commands::start_scrub,
+            commands::cancel_scrub,
+            commands::get_scrub_issues,
+            commands::repair_photo_copy,
             commands::get_photos,
+            commands::get_photo_exif,
+            commands::get_thumbnail,
+            commands::count_photos_without_thumbnails,
+            commands::clear_thumbnail_cache,
             commands::move_photos,
             commands::delete_photos,
             commands::rename_photo,
+            commands::import_photos,
             commands::get_sync_queue_status,
+            commands::verify_sync_status,
+            commands::get_drive_usage,
+            commands::detect_backup_differences,
+            commands::preview_restore,
+            commands::restore_backup_to_primary,
+            commands::preview_mirror_to_backup,
+            commands::mirror_primary_to_backup,
+            commands::cancel_restore,
+            commands::inspect_drive,
+            commands::initialize_drive,
+            commands::adopt_drive,
             commands::create_album,
             commands::add_photos_to_album,
             commands::get_albums,
@@ -35,33 +118,85 @@ pub fn run() {
             commands::add_tag,
             commands::get_all_tags,
             commands::filter_photos,
+            commands::find_duplicates,
+            commands::cancel_find_duplicates,
+            commands::delete_duplicates,
             commands::search_photos
         ])
         .setup(|app| {
             let handle = app.handle().clone();
             tokio::spawn(async move {
-                // Initialize the database
-                let app_data_dir = handle
-                    .path()
-                    .app_data_dir()
-                    .expect("Failed to get app data directory");
-                let db_path = app_data_dir.join("photovault.db");
                 let migrations_path = handle
                     .path()
                     .resolve("migrations", tauri::path::BaseDirectory::Resource)
                     .expect("Failed to resolve migrations path");
 
-                let db_manager = db::manager::DatabaseManager::initialize(
-                    db_path,
-                    None,
-                    &migrations_path,
-                )
-                .await
-                .expect("Failed to initialize database");
+                // Never operate on a volume that is not this library's drive.
+                let config = services::config::load_config().unwrap_or_default();
+                let primary_root =
+                    drive_identity::verified_root(config.primary_path.clone(), config.library_id, DriveRole::Primary);
+                let backup_root =
+                    drive_identity::verified_root(config.backup_path.clone(), config.library_id, DriveRole::Backup);
+
+                if let Some(primary_root) = &primary_root {
+                    // Catalogs used to live in the app data directory.
+                    let legacy_db = handle
+                        .path()
+                        .app_data_dir()
+                        .expect("Failed to get app data directory")
+                        .join("photovault.db");
+                    if let Err(e) = db::import_legacy_catalog(&legacy_db, primary_root).await {
+                        error!("Failed to move the old catalog onto the primary drive: {}", e);
+                    }
+
+                    // The catalogs live on the drives themselves, so either
+                    // drive can be taken to another machine.
+                    match db::manager::DatabaseManager::initialize(
+                        primary_root,
+                        backup_root.as_deref(),
+                        &migrations_path,
+                    )
+                    .await
+                    {
+                        Ok(db_manager) => {
+                            // Older catalogs stored absolute paths. Backup
+                            // catalogs mirrored the primary's, so both are
+                            // relative to the primary root.
+                            for pool in std::iter::once(&db_manager.primary_db).chain(&db_manager.backup_db) {
+                                if let Err(e) = library_path::migrate_absolute_paths(pool, primary_root).await {
+                                    error!("Failed to convert catalog paths: {}", e);
+                                }
+                            }
+                            let app_state: tauri::State<AppState> = handle.state();
+                            let mut sync_engine = app_state.sync_engine.lock().await;
+                            *sync_engine = SyncEngine::new(
+                                db_manager.primary_db,
+                                db_manager.backup_db,
+                                Some(primary_root.clone()),
+                                config.backup_path,
+                            );
+                            restore_queue(&mut sync_engine).await;
+                        }
+                        Err(e) => error!("Failed to open the catalog on {}: {}", primary_root.display(), e),
+                    }
+                } else {
+                    info!("No primary drive is set up yet");
+                }
+
+                tokio::spawn(scrub_when_due(handle.clone()));
 
-                let app_state: tauri::State<AppState> = handle.state();
-                let mut sync_engine = app_state.sync_engine.lock().await;
-                *sync_engine = SyncEngine::new(db_manager.primary_db, db_manager.backup_db);
+                // Watch for the backup drive being plugged in or removed.
+                let mut monitor = DriveMonitor::new(migrations_path);
+                let mut interval = tokio::time::interval(DRIVE_POLL_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let app_state: tauri::State<AppState> = handle.state();
+                    if let Some(event) = monitor.poll(&app_state.sync_engine).await {
+                        if let Err(e) = handle.emit("backup-connection-changed", event) {
+                            error!("Failed to emit backup connection change: {}", e);
+                        }
+                    }
+                }
             });
             Ok(())
         })
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Payload of the `backup-connection-changed` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConnectionEvent {
+    pub connected: bool,
+    pub backup_path: Option<String>,
+    /// Operations still queued after the reconnect flush.
+    pub pending_operations: usize,
+    /// Why a drive mounted at the backup path was not attached, if it wasn't.
+    pub problem: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum DriveRole {
+    Primary,
+    Backup,
+}
+
+impl DriveRole {
+    /// The name stored in catalog columns, matching the serialized form.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DriveRole::Primary => "primary",
+            DriveRole::Backup => "backup",
+        }
+    }
+}
+
+/// Contents of `.photovault/drive.json`, which ties a drive to one library.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DriveIdentity {
+    pub library_id: Uuid,
+    pub drive_id: Uuid,
+    pub role: DriveRole,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Result of checking a mounted volume against the library.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DriveCheck {
+    /// Nothing is mounted at the path.
+    Unavailable,
+    /// The drive has no identity file yet and can be initialized.
+    Uninitialized,
+    Matches { identity: DriveIdentity },
+    /// The drive belongs to a different library and must be adopted explicitly.
+    ForeignLibrary { identity: DriveIdentity },
+    /// The drive belongs to this library but in the other role.
+    WrongRole { identity: DriveIdentity },
+}
+
+/// Space on one library drive, as reported by `get_drive_usage`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DriveUsage {
+    pub role: DriveRole,
+    pub path: Option<String>,
+    pub connected: bool,
+    pub capacity: u64,
+    pub used: u64,
+    pub free: u64,
+    /// Total size of the photos catalogued on the drive.
+    pub library_bytes: u64,
+}
@@ -1,9 +1,65 @@
+use crate::models::operation::PhotoOperationResult;
 use crate::models::photo::Photo;
 use serde::{Deserialize, Serialize};
 
+/// Photos that are copies of one another. The first photo is the one with
+/// the most pixels, then the largest file, the best one to keep.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DuplicateGroup {
+    /// The SHA-256 shared by all photos when `exact`, otherwise the first
+    /// photo's perceptual hash in hex.
     pub hash: String,
     pub photos: Vec<Photo>,
+    /// Bytes freed by keeping only the first photo.
     pub size: u64,
+    /// All files are byte-for-byte identical.
+    pub exact: bool,
+    /// Lowest perceptual similarity, 0 to 1, of a photo to the first one.
+    pub similarity: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateProgress {
+    pub photos_checked: u64,
+    pub photos_total: u64,
+    /// Photos whose file changed since it was last hashed, so it was hashed
+    /// again.
+    pub photos_hashed: u64,
+    pub current_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateReport {
+    /// Largest saving first.
+    pub groups: Vec<DuplicateGroup>,
+    /// Set when the search was stopped early. Groups are then left out, since
+    /// they could be incomplete.
+    pub cancelled: bool,
+    /// Photos no thumbnail can be made of, such as HEIC. They are only
+    /// grouped with byte-for-byte copies, not with similar photos.
+    #[serde(default)]
+    pub photos_unsupported: u64,
+}
+
+/// How `delete_duplicates` picks the photo of a group to keep. Ties go to the
+/// highest resolution, then the largest file, then the photo catalogued first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeeperPolicy {
+    HighestResolution,
+    LargestFile,
+    OldestDateTaken,
+    /// A photo in this folder, relative to the library root, or one of its
+    /// subfolders.
+    PreferredFolder { folder: String },
+    MostAlbums,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateCleanup {
+    pub kept: Vec<i64>,
+    pub deleted: Vec<i64>,
+    /// Size on the primary drive of the files that were deleted.
+    pub bytes_freed: u64,
+    pub failed: Vec<PhotoOperationResult>,
 }
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PhotoExif {
+    pub photo_id: i64,
+    pub date_time_original: Option<String>,
+    pub offset_time_original: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    pub iso: Option<u32>,
+    pub aperture: Option<f64>,
+    pub exposure_time: Option<f64>,
+    pub focal_length: Option<f64>,
+    pub orientation: Option<u32>,
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
+    pub gps_altitude: Option<f64>,
+}
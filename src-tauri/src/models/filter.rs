@@ -1,3 +1,4 @@
+use crate::models::photo::Photo;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
@@ -8,6 +9,46 @@ pub struct FilterCriteria {
     pub min_width: Option<u32>,
     pub min_height: Option<u32>,
     pub tags: Option<Vec<i64>>,
+    #[serde(default)]
+    pub tag_match: TagMatch,
+    /// Photos in any of these albums.
     pub albums: Option<Vec<i64>>,
+    /// Words that must each appear in the photo's path or one of its tags.
     pub query: Option<String>,
+    #[serde(default)]
+    pub sort: PhotoSort,
+    #[serde(default)]
+    pub descending: bool,
+    /// `next_cursor` of the previous page.
+    pub after: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMatch {
+    /// Photos with at least one of the tags.
+    #[default]
+    Any,
+    /// Photos with every one of the tags.
+    All,
+}
+
+/// Order of filtered photos. Photos with the same key are in catalog order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhotoSort {
+    /// Photos without a date come first.
+    #[default]
+    DateTaken,
+    DateAdded,
+    Filename,
+    FileSize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhotoPage {
+    pub photos: Vec<Photo>,
+    /// Pass as `after` to get the next page; `None` on the last page.
+    pub next_cursor: Option<i64>,
 }
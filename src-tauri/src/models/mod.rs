@@ -6,3 +6,8 @@ pub mod filter;
 pub mod duplicate;
 pub mod rename;
 pub mod restore;
+pub mod scan;
+pub mod exif;
+pub mod thumbnail;
+pub mod drive;
+pub mod scrub;
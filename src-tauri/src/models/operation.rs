@@ -1,12 +1,47 @@
+use crate::error::PhotoVaultError;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
 
+/// A change to the library. Paths are catalog paths, relative to the library
+/// root, so the same operation applies to either drive.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Operation {
-    Move { from: PathBuf, to: PathBuf },
-    Delete { path: PathBuf },
-    Rename { path: PathBuf, new_name: String },
+    Move { from: String, to: String },
+    Delete { path: String },
+    Rename { path: String, new_name: String },
+    /// Copies a file from outside the library, `source` being an absolute
+    /// path, to `path` on both drives.
+    Import { source: String, path: String },
     CreateAlbum { name: String },
-    AddToAlbum { photo_id: i64, album_id: i64 },
-    AddTag { photo_id: i64, tag_name: String },
+    /// Adds the photo at `path` to the album named `album`. Each drive has
+    /// its own catalog, so rows are found by path and name, never by id.
+    AddToAlbum { path: String, album: String },
+    /// Tags the photo at `path`, creating the tag if needed.
+    AddTag { path: String, tag_name: String },
+    /// Adds the photo at `into` to every album and tag of the photo at
+    /// `from`, before `from` is deleted as a duplicate of it.
+    MergeLabels { from: String, into: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhotoOperationResult {
+    pub photo_id: i64,
+    pub success: bool,
+    pub error: Option<PhotoVaultError>,
+}
+
+/// An operation already applied to the primary drive that the backup drive
+/// has yet to receive. `id` is its row in `sync_operations`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedOperation {
+    pub id: String,
+    pub operation: Operation,
+}
+
+/// Outcome of importing one file through `import_photos`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub source: String,
+    /// Catalog path of the imported photo, when the import succeeded.
+    pub path: Option<String>,
+    pub error: Option<PhotoVaultError>,
 }
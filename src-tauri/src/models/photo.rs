@@ -1,14 +1,43 @@
+use crate::models::exif::PhotoExif;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Photo {
     pub id: i64,
     pub path: String,
     pub filename: String,
+    pub file_hash: String,
     pub file_size: u64,
     pub date_taken: Option<DateTime<Utc>>,
     pub width: u32,
     pub height: u32,
     pub format: String,
 }
+
+// Written by hand because sqlx cannot decode `u64` from SQLite and several
+// columns are nullable in the schema.
+impl<'r> FromRow<'r, SqliteRow> for Photo {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Photo {
+            id: row.try_get("id")?,
+            path: row.try_get("path")?,
+            filename: row.try_get("filename")?,
+            file_hash: row.try_get("file_hash")?,
+            file_size: row.try_get::<Option<i64>, _>("file_size")?.unwrap_or(0) as u64,
+            date_taken: row.try_get("date_taken")?,
+            width: row.try_get::<Option<u32>, _>("width")?.unwrap_or(0),
+            height: row.try_get::<Option<u32>, _>("height")?.unwrap_or(0),
+            format: row.try_get::<Option<String>, _>("format")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// Everything read from a photo file during a scan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhotoMetadata {
+    pub photo: Photo,
+    pub exif: Option<PhotoExif>,
+}
@@ -1,7 +1,144 @@
+use crate::error::PhotoVaultError;
+use crate::models::drive::DriveRole;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// How a file on one drive differs from the other drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifferenceKind {
+    /// Catalogued and present on the primary, absent from the backup.
+    MissingOnBackup,
+    /// Catalogued and present on the backup, absent from the primary.
+    MissingOnPrimary,
+    /// On both drives, but the files are not the same.
+    ContentDiffers,
+    /// The files match but the catalogs disagree about them.
+    MetadataDiffers,
+    /// On one drive only, and no catalog knows about it.
+    ExtraUntracked,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileDifference {
+    /// Catalog path, relative to the library root.
+    pub path: String,
+    pub kind: DifferenceKind,
+    /// The drive an untracked file was found on.
+    pub drive: Option<DriveRole>,
+    pub primary_size: Option<u64>,
+    pub backup_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RestoreSummary {
-    pub missing_files: i64,
-    pub different_files: i64,
+    pub missing_on_backup: u64,
+    pub missing_on_primary: u64,
+    pub content_differs: u64,
+    pub metadata_differs: u64,
+    pub extra_untracked: u64,
+}
+
+impl RestoreSummary {
+    pub fn count(&mut self, kind: DifferenceKind) {
+        let counter = match kind {
+            DifferenceKind::MissingOnBackup => &mut self.missing_on_backup,
+            DifferenceKind::MissingOnPrimary => &mut self.missing_on_primary,
+            DifferenceKind::ContentDiffers => &mut self.content_differs,
+            DifferenceKind::MetadataDiffers => &mut self.metadata_differs,
+            DifferenceKind::ExtraUntracked => &mut self.extra_untracked,
+        };
+        *counter += 1;
+    }
+}
+
+/// One page of the differences between the drives, sorted by path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreReport {
+    /// Counts over all differences, whatever page or kind was asked for.
+    pub summary: RestoreSummary,
+    pub differences: Vec<FileDifference>,
+    /// Number of differences of the requested kind across all pages.
+    pub total: u64,
+}
+
+/// Which of the backup's photos a restore brings back to the primary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RestoreSelection {
+    /// Every photo the primary is missing, or holds a different copy or
+    /// catalog entry of.
+    AllDifferences,
+    /// Photos by their id in the primary catalog.
+    Photos { photo_ids: Vec<i64> },
+    /// Photos in a folder, relative to the library root, and its subfolders.
+    Folder { folder: String },
+    /// Photos taken in `[from, to)`. An open end is unbounded.
+    DateRange {
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    },
+}
+
+/// What a restore does to one file on the drive it restores, the primary
+/// when restoring from the backup and the backup when mirroring the primary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreAction {
+    /// The drive has no file at the path; the other drive's copy is added.
+    CopyFile,
+    /// The drive's file differs or failed its integrity check; it is
+    /// overwritten with the other drive's copy.
+    ReplaceFile,
+    /// The files match; only the catalog entry, albums and tags are copied.
+    UpdateCatalog,
+    /// The file is not on the other drive; it is moved into the drive's
+    /// quarantine folder rather than deleted.
+    Quarantine,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreChange {
+    pub path: String,
+    pub action: RestoreAction,
+    /// Bytes copied to the drive; 0 when no file is copied.
+    pub size: u64,
+}
+
+/// What a restore would do, worked out without changing anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestorePlan {
+    pub changes: Vec<RestoreChange>,
+    /// Selected photos that cannot be restored, such as those whose file is
+    /// missing from the backup. A restore reports them as failed.
+    pub skipped: Vec<RestoreFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreProgress {
+    pub files_total: u64,
+    pub files_done: u64,
+    pub bytes_total: u64,
+    pub bytes_done: u64,
+    pub current_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreFailure {
+    pub path: String,
+    pub error: PhotoVaultError,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreOutcome {
+    pub restored: Vec<RestoreChange>,
+    pub failed: Vec<RestoreFailure>,
+    /// Set when the restore was stopped early. Changes made up to that point
+    /// are kept.
+    pub cancelled: bool,
+    /// Set when a mirror left the backup catalog behind its files: it was
+    /// not rewritten because the mirror was cancelled, or it leaves out the
+    /// files that failed.
+    #[serde(default)]
+    pub catalog_stale: bool,
 }
@@ -0,0 +1,38 @@
+use crate::models::photo::Photo;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanSummary {
+    pub added: Vec<Photo>,
+    pub updated: Vec<Photo>,
+    pub removed: Vec<String>,
+    pub unchanged: u64,
+    pub errors: Vec<ScanError>,
+    /// Set when the scan was stopped early. Photos processed up to that point
+    /// are kept, but no files are reported as removed.
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanPhase {
+    Discovering,
+    Processing,
+    Finished,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanProgress {
+    pub phase: ScanPhase,
+    pub files_discovered: u64,
+    pub files_processed: u64,
+    pub current_path: Option<String>,
+    pub error_count: u64,
+    pub eta_seconds: Option<u64>,
+}
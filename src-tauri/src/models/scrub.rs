@@ -0,0 +1,58 @@
+use crate::models::drive::DriveRole;
+use serde::{Deserialize, Serialize};
+
+/// What a check found for a photo's file on one drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum FileStatus {
+    Ok,
+    Corrupt,
+    Missing,
+    /// The file no longer has the size or modification time it was
+    /// catalogued with, so it was most likely edited on purpose. A rescan
+    /// picks up the new contents; repairing it would undo the edit.
+    Modified,
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileStatus::Ok => "ok",
+            FileStatus::Corrupt => "corrupt",
+            FileStatus::Missing => "missing",
+            FileStatus::Modified => "modified",
+        }
+    }
+}
+
+/// A photo whose file on `drive` failed its last check.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ScrubIssue {
+    pub photo_id: i64,
+    pub path: String,
+    pub drive: DriveRole,
+    pub status: FileStatus,
+    /// The copy on the other drive passed its last check and can replace it.
+    /// Never set for modified files.
+    pub repairable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrubProgress {
+    pub photos_checked: u64,
+    pub photos_total: u64,
+    pub issues_found: u64,
+    pub current_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScrubSummary {
+    pub photos_checked: u64,
+    /// Every file that currently fails its check, not only those found in
+    /// this run.
+    pub issues: Vec<ScrubIssue>,
+    /// Set when the scrub was stopped early. The next run resumes after the
+    /// last photo checked.
+    pub cancelled: bool,
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailSize {
+    Grid,
+    Preview,
+}
+
+impl ThumbnailSize {
+    /// Longest edge of the generated thumbnail, in pixels.
+    pub fn max_dimension(self) -> u32 {
+        match self {
+            ThumbnailSize::Grid => 256,
+            ThumbnailSize::Preview => 1600,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThumbnailSize::Grid => "grid",
+            ThumbnailSize::Preview => "preview",
+        }
+    }
+}
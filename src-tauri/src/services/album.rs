@@ -1,3 +1,4 @@
+use crate::error::Result;
 use crate::models::album::Album;
 use sqlx::SqlitePool;
 
@@ -10,16 +11,17 @@ impl AlbumService {
         Self { pool }
     }
 
-    pub async fn create_album(&self, name: String) -> Result<Album, sqlx::Error> {
+    /// Creates the album `name`, or returns it if it already exists so that
+    /// replaying the operation is harmless.
+    pub async fn create_album(&self, name: String) -> Result<Album> {
         let mut conn = self.pool.acquire().await?;
-        let id = sqlx::query("INSERT INTO albums (name) VALUES (?)")
+        sqlx::query("INSERT OR IGNORE INTO albums (name) VALUES (?)")
             .bind(&name)
             .execute(&mut *conn)
-            .await?
-            .last_insert_rowid();
+            .await?;
 
-        let album = sqlx::query_as::<_, Album>("SELECT * FROM albums WHERE id = ?")
-            .bind(id)
+        let album = sqlx::query_as::<_, Album>("SELECT * FROM albums WHERE name = ?")
+            .bind(&name)
             .fetch_one(&mut *conn)
             .await?;
         Ok(album)
@@ -29,10 +31,12 @@ impl AlbumService {
         &self,
         photo_ids: Vec<i64>,
         album_id: i64,
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
+        // Photos already in the album stay as they are, so replaying an
+        // operation is harmless.
         for photo_id in photo_ids {
-            sqlx::query("INSERT INTO photo_album (photo_id, album_id) VALUES (?, ?)")
+            sqlx::query("INSERT OR IGNORE INTO photo_album (photo_id, album_id) VALUES (?, ?)")
                 .bind(photo_id)
                 .bind(album_id)
                 .execute(&mut *conn)
@@ -45,7 +49,7 @@ impl AlbumService {
         &self,
         photo_ids: Vec<i64>,
         album_id: i64,
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
         for photo_id in photo_ids {
             sqlx::query("DELETE FROM photo_album WHERE photo_id = ? AND album_id = ?")
@@ -57,7 +61,7 @@ impl AlbumService {
         Ok(())
     }
 
-    pub async fn delete_album(&self, album_id: i64) -> Result<(), sqlx::Error> {
+    pub async fn delete_album(&self, album_id: i64) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
         sqlx::query("DELETE FROM photo_album WHERE album_id = ?")
             .bind(album_id)
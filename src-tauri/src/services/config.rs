@@ -1,10 +1,14 @@
+use crate::error::{PhotoVaultError, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AppConfig {
     pub primary_path: Option<PathBuf>,
     pub backup_path: Option<PathBuf>,
+    /// Identifies the library; both drives carry it in their identity file.
+    pub library_id: Option<Uuid>,
 }
 
 impl Default for AppConfig {
@@ -12,31 +16,32 @@ impl Default for AppConfig {
         AppConfig {
             primary_path: None,
             backup_path: None,
+            library_id: None,
         }
     }
 }
 
-pub fn get_config_path() -> Result<PathBuf, String> {
+pub fn get_config_path() -> Result<PathBuf> {
     let config_dir = dirs::home_dir()
-        .ok_or("Could not find home directory".to_string())?
+        .ok_or_else(|| PhotoVaultError::not_found("Home directory"))?
         .join(".photovault");
-    std::fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&config_dir).map_err(|e| PhotoVaultError::io(&config_dir, e))?;
     Ok(config_dir.join("config.json"))
 }
 
-pub fn load_config() -> Result<AppConfig, String> {
+pub fn load_config() -> Result<AppConfig> {
     let config_path = get_config_path()?;
     if !config_path.exists() {
         return Ok(AppConfig::default());
     }
-    let config_str = std::fs::read_to_string(config_path).map_err(|e| e.to_string())?;
-    serde_json::from_str(&config_str).map_err(|e| e.to_string())
+    let config_str = std::fs::read_to_string(&config_path).map_err(|e| PhotoVaultError::io(&config_path, e))?;
+    Ok(serde_json::from_str(&config_str)?)
 }
 
-pub fn save_config(config: &AppConfig) -> Result<(), String> {
+pub fn save_config(config: &AppConfig) -> Result<()> {
     let config_path = get_config_path()?;
-    let config_str = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
-    std::fs::write(config_path, config_str).map_err(|e| e.to_string())
+    let config_str = serde_json::to_string_pretty(config)?;
+    std::fs::write(&config_path, config_str).map_err(|e| PhotoVaultError::io(&config_path, e))
 }
 
 
@@ -0,0 +1,180 @@
+use crate::error::{PhotoVaultError, Result};
+use crate::models::drive::{DriveCheck, DriveIdentity, DriveRole};
+use chrono::Utc;
+use log::warn;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub fn identity_path(drive_root: &Path) -> PathBuf {
+    drive_root.join(".photovault").join("drive.json")
+}
+
+pub fn read_identity(drive_root: &Path) -> Result<Option<DriveIdentity>> {
+    let path = identity_path(drive_root);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|e| PhotoVaultError::io(&path, e))?;
+    serde_json::from_str(&contents).map(Some).map_err(|e| PhotoVaultError::DriveMismatch {
+        path: drive_root.display().to_string(),
+        reason: format!("invalid drive identity: {}", e),
+    })
+}
+
+pub fn write_identity(drive_root: &Path, identity: &DriveIdentity) -> Result<()> {
+    let path = identity_path(drive_root);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| PhotoVaultError::io(parent, e))?;
+    }
+    let contents = serde_json::to_string_pretty(identity)?;
+    std::fs::write(&path, contents).map_err(|e| PhotoVaultError::io(&path, e))
+}
+
+/// Checks whether the volume mounted at `drive_root` may be used in `role`
+/// for the library `library_id`.
+pub fn check_drive(drive_root: &Path, library_id: Option<Uuid>, role: DriveRole) -> Result<DriveCheck> {
+    if !drive_root.is_dir() {
+        return Ok(DriveCheck::Unavailable);
+    }
+    let Some(identity) = read_identity(drive_root)? else {
+        return Ok(DriveCheck::Uninitialized);
+    };
+    Ok(if Some(identity.library_id) != library_id {
+        DriveCheck::ForeignLibrary { identity }
+    } else if identity.role != role {
+        DriveCheck::WrongRole { identity }
+    } else {
+        DriveCheck::Matches { identity }
+    })
+}
+
+/// Writes a fresh identity to a drive that has none. Drives that already
+/// carry an identity have to go through `adopt_drive` instead.
+pub fn initialize_drive(drive_root: &Path, library_id: Uuid, role: DriveRole) -> Result<DriveIdentity> {
+    if !drive_root.is_dir() {
+        return Err(PhotoVaultError::DriveOffline { drive: role });
+    }
+    if read_identity(drive_root)?.is_some() {
+        return Err(PhotoVaultError::conflict(format!(
+            "{} already belongs to a PhotoVault library; adopt it instead",
+            drive_root.display()
+        )));
+    }
+    let identity = DriveIdentity {
+        library_id,
+        drive_id: Uuid::new_v4(),
+        role,
+        created_at: Utc::now(),
+    };
+    write_identity(drive_root, &identity)?;
+    Ok(identity)
+}
+
+/// Rewrites a drive's identity so it belongs to `library_id` in `role`,
+/// keeping its drive id and creation date when it had an identity already.
+pub fn adopt_drive(drive_root: &Path, library_id: Uuid, role: DriveRole) -> Result<DriveIdentity> {
+    if !drive_root.is_dir() {
+        return Err(PhotoVaultError::DriveOffline { drive: role });
+    }
+    let identity = match read_identity(drive_root)? {
+        Some(existing) => DriveIdentity {
+            library_id,
+            role,
+            ..existing
+        },
+        None => DriveIdentity {
+            library_id,
+            drive_id: Uuid::new_v4(),
+            role,
+            created_at: Utc::now(),
+        },
+    };
+    write_identity(drive_root, &identity)?;
+    Ok(identity)
+}
+
+/// Returns `path` if it holds this library's drive in `role`, logging why not
+/// otherwise.
+pub fn verified_root(path: Option<PathBuf>, library_id: Option<Uuid>, role: DriveRole) -> Option<PathBuf> {
+    let path = path?;
+    match ensure_drive(&path, library_id, role) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!("Ignoring {:?} drive: {}", role, e);
+            None
+        }
+    }
+}
+
+/// Fails unless the drive at `path` carries this library's identity in `role`.
+pub fn ensure_drive(path: &Path, library_id: Option<Uuid>, role: DriveRole) -> Result<()> {
+    let check = check_drive(path, library_id, role)?;
+    if check == DriveCheck::Unavailable {
+        return Err(PhotoVaultError::DriveOffline { drive: role });
+    }
+    match describe_problem(&check, path) {
+        Some(reason) => Err(PhotoVaultError::DriveMismatch {
+            path: path.display().to_string(),
+            reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// A short explanation of why a drive cannot be used, for logs and events.
+pub fn describe_problem(check: &DriveCheck, drive_root: &Path) -> Option<String> {
+    match check {
+        DriveCheck::Matches { .. } => None,
+        DriveCheck::Unavailable => Some(format!("{} is not mounted", drive_root.display())),
+        DriveCheck::Uninitialized => Some(format!(
+            "{} has not been initialized for this library",
+            drive_root.display()
+        )),
+        DriveCheck::ForeignLibrary { identity } => Some(format!(
+            "{} belongs to another library ({})",
+            drive_root.display(),
+            identity.library_id
+        )),
+        DriveCheck::WrongRole { identity } => Some(format!(
+            "{} is this library's {:?} drive",
+            drive_root.display(),
+            identity.role
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_drive_requires_matching_identity() {
+        let dir = tempdir().unwrap();
+        let library_id = Uuid::new_v4();
+
+        let check = check_drive(dir.path(), Some(library_id), DriveRole::Backup).unwrap();
+        assert_eq!(check, DriveCheck::Uninitialized);
+
+        let identity = initialize_drive(dir.path(), library_id, DriveRole::Backup).unwrap();
+        let check = check_drive(dir.path(), Some(library_id), DriveRole::Backup).unwrap();
+        assert_eq!(check, DriveCheck::Matches { identity: identity.clone() });
+        let check = check_drive(dir.path(), Some(library_id), DriveRole::Primary).unwrap();
+        assert!(matches!(check, DriveCheck::WrongRole { .. }));
+
+        // A drive from another library is refused until it is adopted.
+        let other_library = Uuid::new_v4();
+        let check = check_drive(dir.path(), Some(other_library), DriveRole::Backup).unwrap();
+        assert!(matches!(check, DriveCheck::ForeignLibrary { .. }));
+        assert!(initialize_drive(dir.path(), other_library, DriveRole::Backup).is_err());
+
+        let adopted = adopt_drive(dir.path(), other_library, DriveRole::Backup).unwrap();
+        assert_eq!(adopted.drive_id, identity.drive_id);
+        let check = check_drive(dir.path(), Some(other_library), DriveRole::Backup).unwrap();
+        assert!(matches!(check, DriveCheck::Matches { .. }));
+
+        let missing = dir.path().join("unplugged");
+        let check = check_drive(&missing, Some(other_library), DriveRole::Backup).unwrap();
+        assert_eq!(check, DriveCheck::Unavailable);
+    }
+}
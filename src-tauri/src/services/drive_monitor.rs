@@ -0,0 +1,99 @@
+use crate::db;
+use crate::models::drive::{BackupConnectionEvent, DriveCheck, DriveRole};
+use crate::services::config::load_config;
+use crate::services::drive_identity;
+use crate::services::library_path;
+use crate::services::sync_engine::SyncEngine;
+use log::{info, warn};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// How often the configured backup location is checked.
+pub const DRIVE_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Watches the configured backup location and attaches or detaches the
+/// backup drive on the `SyncEngine` as it appears and disappears.
+pub struct DriveMonitor {
+    migrations_path: PathBuf,
+    /// Last reason a mounted drive was refused, so it is reported only once.
+    problem: Option<String>,
+}
+
+impl DriveMonitor {
+    pub fn new(migrations_path: PathBuf) -> Self {
+        Self {
+            migrations_path,
+            problem: None,
+        }
+    }
+
+    /// Checks the backup location once. Returns an event when the connection
+    /// state changed. The queue is flushed right after a reconnect.
+    ///
+    /// A drive is only attached when its identity file names this library
+    /// and the backup role; anything else mounted at the path is left alone.
+    pub async fn poll(&mut self, engine: &Mutex<SyncEngine>) -> Option<BackupConnectionEvent> {
+        // Re-read the config every time so a newly chosen backup drive is
+        // picked up without a restart.
+        let config = load_config().unwrap_or_default();
+        let (available, problem) = match config.backup_path {
+            Some(path) => match drive_identity::check_drive(&path, config.library_id, DriveRole::Backup) {
+                Ok(DriveCheck::Matches { .. }) => (Some(path), None),
+                Ok(DriveCheck::Unavailable) => (None, None),
+                Ok(check) => (None, drive_identity::describe_problem(&check, &path)),
+                Err(e) => (None, Some(e.to_string())),
+            },
+            None => (None, None),
+        };
+
+        let mut engine = engine.lock().await;
+        let attached = engine.backup_db.as_ref().and(engine.backup_root.clone());
+        if available == attached && problem == self.problem {
+            return None;
+        }
+        if let Some(problem) = &problem {
+            if self.problem.as_ref() != Some(problem) {
+                warn!("Not attaching backup drive: {}", problem);
+            }
+        }
+        self.problem = problem.clone();
+        if available == attached {
+            return Some(self.event(&engine));
+        }
+
+        if let Some(root) = attached {
+            info!("Backup drive at {} disconnected", root.display());
+            engine.detach_backup().await;
+        }
+        if let Some(root) = available {
+            match db::open_catalog(&root, &self.migrations_path).await {
+                Ok(pool) => {
+                    info!("Backup drive at {} connected", root.display());
+                    // Older backup catalogs mirrored the primary's absolute paths.
+                    if let Some(primary_root) = &engine.primary_root {
+                        if let Err(e) = library_path::migrate_absolute_paths(&pool, primary_root).await {
+                            warn!("Failed to convert backup catalog paths: {}", e);
+                        }
+                    }
+                    engine.attach_backup(root, pool);
+                    if let Err(e) = engine.flush_queue().await {
+                        warn!("Failed to flush queue after reconnect: {}", e);
+                    }
+                }
+                Err(e) => warn!("Failed to open backup catalog on {}: {}", root.display(), e),
+            }
+        }
+
+        Some(self.event(&engine))
+    }
+
+    fn event(&self, engine: &SyncEngine) -> BackupConnectionEvent {
+        BackupConnectionEvent {
+            connected: engine.backup_db.is_some(),
+            backup_path: engine.backup_root.as_ref().map(|root| root.to_string_lossy().into_owned()),
+            pending_operations: engine.operation_queue.len(),
+            problem: self.problem.clone(),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::error::{PhotoVaultError, Result};
+use crate::models::drive::{DriveRole, DriveUsage};
+use sqlx::SqlitePool;
+use std::path::Path;
+
+/// Kept free on top of what an operation needs, so the catalog, staging
+/// area and thumbnail cache on the drive can still grow.
+pub const SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// Fails with `InsufficientSpace` unless `required` bytes plus the margin
+/// fit on the drive at `root`.
+pub fn ensure_space(root: &Path, drive: DriveRole, required: u64) -> Result<()> {
+    if required == 0 {
+        return Ok(());
+    }
+    let available = fs2::available_space(root).map_err(|e| PhotoVaultError::io(root, e))?;
+    if available < required.saturating_add(SPACE_MARGIN) {
+        return Err(PhotoVaultError::InsufficientSpace {
+            drive,
+            path: root.display().to_string(),
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Reports capacity and free space of a drive and how much of it the
+/// catalogued photos take up. A drive that is not mounted reports zeros.
+pub async fn drive_usage(drive: DriveRole, root: Option<&Path>, catalog: Option<&SqlitePool>) -> Result<DriveUsage> {
+    let mounted = root.filter(|root| root.is_dir());
+    let (capacity, free) = match mounted {
+        Some(root) => (
+            fs2::total_space(root).map_err(|e| PhotoVaultError::io(root, e))?,
+            fs2::available_space(root).map_err(|e| PhotoVaultError::io(root, e))?,
+        ),
+        None => (0, 0),
+    };
+    let library_bytes: i64 = match catalog {
+        Some(pool) if mounted.is_some() => {
+            sqlx::query_scalar("SELECT COALESCE(SUM(file_size), 0) FROM photos")
+                .fetch_one(pool)
+                .await?
+        }
+        _ => 0,
+    };
+    Ok(DriveUsage {
+        role: drive,
+        path: root.map(|root| root.display().to_string()),
+        connected: mounted.is_some(),
+        capacity,
+        used: capacity.saturating_sub(free),
+        free,
+        library_bytes: library_bytes as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_ensure_space_reports_the_drive_that_is_full() {
+        let drive = tempdir().unwrap();
+        ensure_space(drive.path(), DriveRole::Backup, 0).unwrap();
+        ensure_space(drive.path(), DriveRole::Backup, 1).unwrap();
+
+        // More than any disk holds.
+        let required = u64::MAX / 2;
+        match ensure_space(drive.path(), DriveRole::Backup, required) {
+            Err(PhotoVaultError::InsufficientSpace { drive: role, path, required: asked, available }) => {
+                assert_eq!(role, DriveRole::Backup);
+                assert_eq!(path, drive.path().display().to_string());
+                assert_eq!(asked, required);
+                assert!(available < required);
+            }
+            other => panic!("expected InsufficientSpace, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drive_usage_is_consistent_and_zero_when_unmounted() {
+        let (drive, db_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = crate::db::init_db(&db_dir.path().join("test.db"), &migrations).await.unwrap();
+        sqlx::query(
+            "INSERT INTO photos (path, filename, file_hash, file_size)
+             VALUES ('a.jpg', 'a.jpg', 'a', 1000), ('b.jpg', 'b.jpg', 'b', 234), ('c.jpg', 'c.jpg', 'c', NULL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let usage = drive_usage(DriveRole::Primary, Some(drive.path()), Some(&pool)).await.unwrap();
+        assert!(usage.connected);
+        assert_eq!(usage.role, DriveRole::Primary);
+        assert!(usage.capacity > 0);
+        assert!(usage.free <= usage.capacity);
+        assert_eq!(usage.used + usage.free, usage.capacity);
+        assert_eq!(usage.library_bytes, 1234);
+
+        let missing = drive.path().join("not mounted");
+        let usage = drive_usage(DriveRole::Backup, Some(&missing), Some(&pool)).await.unwrap();
+        assert!(!usage.connected);
+        assert_eq!((usage.capacity, usage.used, usage.free, usage.library_bytes), (0, 0, 0, 0));
+    }
+}
@@ -1,21 +1,538 @@
-use crate::models::duplicate::DuplicateGroup;
-use std::path::Path;
+use crate::error::{PhotoVaultError, Result};
+use crate::models::drive::DriveRole;
+use crate::models::duplicate::{DuplicateCleanup, DuplicateGroup, DuplicateProgress, DuplicateReport, KeeperPolicy};
+use crate::models::operation::{Operation, PhotoOperationResult};
+use crate::models::photo::Photo;
+use crate::models::thumbnail::ThumbnailSize;
+use crate::services::file_ops::modified_millis;
+use crate::services::library_path;
+use crate::services::similarity::{self, BkTree};
+use crate::services::sync_engine::SyncEngine;
+use crate::services::thumbnail::ThumbnailService;
+use log::warn;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-pub struct DuplicateDetector;
+/// Bytes read from a file at a time while hashing it.
+const HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
+pub type DuplicateProgressCallback = Arc<dyn Fn(&DuplicateProgress) + Send + Sync>;
+
+/// Finds photos that are copies of one another. File hashes are cached in
+/// `photos.file_hash` with the size and modification time of the file they
+/// were computed from, and perceptual hashes with the file hash they belong
+/// to, so only files that changed since are read again.
+pub struct DuplicateDetector {
+    pool: SqlitePool,
+    primary_root: PathBuf,
+}
 
 impl DuplicateDetector {
-    pub async fn find_duplicates(threshold: f32) -> Result<Vec<DuplicateGroup>, String> {
-        // Logic to find duplicates
-        Ok(vec![])
+    pub fn new(pool: SqlitePool, primary_root: PathBuf) -> Self {
+        Self { pool, primary_root }
+    }
+
+    /// Groups photos that are identical, or whose perceptual similarity is
+    /// at least `threshold` (0 to 1), such as resized or recompressed copies
+    /// of the same shot. Photos without a thumbnail, such as HEIC, only
+    /// match identical files and are counted in `photos_unsupported`.
+    pub async fn find_duplicates(
+        &self,
+        threshold: f32,
+        cancel: Arc<AtomicBool>,
+        on_progress: DuplicateProgressCallback,
+    ) -> Result<DuplicateReport> {
+        let photos = sqlx::query_as::<_, Photo>("SELECT * FROM photos ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut progress = DuplicateProgress {
+            photos_checked: 0,
+            photos_total: photos.len() as u64,
+            photos_hashed: 0,
+            current_path: None,
+        };
+        let mut checked: Vec<(Photo, Option<u64>)> = Vec::with_capacity(photos.len());
+        let mut photos_unsupported = 0;
+        for mut photo in photos {
+            if cancel.load(Ordering::Relaxed) {
+                return Ok(DuplicateReport {
+                    cancelled: true,
+                    ..DuplicateReport::default()
+                });
+            }
+            progress.current_path = Some(photo.path.clone());
+            on_progress(&progress);
+
+            match self.current_hash(&photo, &cancel).await {
+                Ok(Some((hash, size, rehashed))) => {
+                    if rehashed {
+                        progress.photos_hashed += 1;
+                    }
+                    photo.file_hash = hash;
+                    photo.file_size = size;
+                }
+                // Cancelled halfway through the file.
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Skipping {} while looking for duplicates: {}", photo.path, e);
+                    continue;
+                }
+            }
+            let perceptual_hash = match self.perceptual_hash(&photo).await {
+                Ok(hash) => Some(hash),
+                Err(PhotoVaultError::UnsupportedFormat { .. }) => {
+                    photos_unsupported += 1;
+                    None
+                }
+                Err(e) => {
+                    warn!("No perceptual hash for {}: {}", photo.path, e);
+                    None
+                }
+            };
+            checked.push((photo, perceptual_hash));
+            progress.photos_checked += 1;
+        }
+        progress.current_path = None;
+        on_progress(&progress);
+
+        let mut groups = group_duplicates(checked, similarity::max_distance(threshold));
+        groups.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.hash.cmp(&b.hash)));
+        Ok(DuplicateReport {
+            groups,
+            cancelled: cancel.load(Ordering::Relaxed),
+            photos_unsupported,
+        })
+    }
+
+    /// The photo's perceptual hash, computed from its grid thumbnail unless
+    /// the catalog has one for the current file.
+    async fn perceptual_hash(&self, photo: &Photo) -> Result<u64> {
+        let cached: Option<(Option<i64>, Option<String>)> =
+            sqlx::query_as("SELECT perceptual_hash, perceptual_hash_of FROM photos WHERE id = ?")
+                .bind(photo.id)
+                .fetch_optional(&self.pool)
+                .await?;
+        if let Some((Some(hash), Some(hash_of))) = cached {
+            if hash_of == photo.file_hash {
+                return Ok(hash as u64);
+            }
+        }
+
+        let path = library_path::resolve(&self.primary_root, &photo.path)?;
+        let thumbnail = ThumbnailService::new(&self.primary_root)
+            .generate_thumbnail(&path, &photo.file_hash, ThumbnailSize::Grid)
+            .await?;
+        let hash = tokio::task::spawn_blocking(move || -> Result<u64> {
+            Ok(similarity::dhash(&image::load_from_memory(&thumbnail)?))
+        })
+        .await??;
+        sqlx::query("UPDATE photos SET perceptual_hash = ?, perceptual_hash_of = ? WHERE id = ?")
+            .bind(hash as i64)
+            .bind(&photo.file_hash)
+            .bind(photo.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(hash)
     }
 
-    pub async fn hash_file(path: &Path) -> Result<String, String> {
-        // Logic to hash a file
-        Ok("".to_string())
+    /// The photo's hash and size, from the cache if its file is unchanged.
+    /// Returns whether the file had to be hashed again, or `None` if the
+    /// search was cancelled while hashing it.
+    async fn current_hash(&self, photo: &Photo, cancel: &Arc<AtomicBool>) -> Result<Option<(String, u64, bool)>> {
+        let path = library_path::resolve(&self.primary_root, &photo.path)?;
+        let metadata = tokio::fs::metadata(&path).await.map_err(|e| PhotoVaultError::io(&path, e))?;
+        let (size, mtime) = (metadata.len(), modified_millis(&metadata));
+        let cached: Option<i64> = sqlx::query_scalar("SELECT file_mtime FROM photos WHERE id = ?")
+            .bind(photo.id)
+            .fetch_one(&self.pool)
+            .await?;
+        if !photo.file_hash.is_empty() && photo.file_size == size && cached == Some(mtime) {
+            return Ok(Some((photo.file_hash.clone(), size, false)));
+        }
+        let Some(hash) = Self::hash_file(&path, cancel.clone()).await? else {
+            return Ok(None);
+        };
+        self.cache_hash(photo.id, &hash, size, mtime).await?;
+        Ok(Some((hash, size, true)))
     }
 
-    pub async fn cache_hash(photo_id: i64, hash: String) -> Result<(), String> {
-        // Logic to cache the hash in the database
+    /// SHA-256 of a file, read in chunks so large files are never held in
+    /// memory. Returns `None` if `cancel` is set before it is done.
+    pub async fn hash_file(path: &Path, cancel: Arc<AtomicBool>) -> Result<Option<String>> {
+        let file = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let mut reader = File::open(&file).map_err(|e| PhotoVaultError::io(&file, e))?;
+            let mut hasher = Sha256::new();
+            let mut chunk = vec![0; HASH_CHUNK_SIZE];
+            loop {
+                if cancel.load(Ordering::Relaxed) {
+                    return Ok(None);
+                }
+                let read = reader.read(&mut chunk).map_err(|e| PhotoVaultError::io(&file, e))?;
+                if read == 0 {
+                    return Ok(Some(format!("{:x}", hasher.finalize())));
+                }
+                hasher.update(&chunk[..read]);
+            }
+        })
+        .await?
+    }
+
+    /// Stores a photo's hash along with the size and modification time of
+    /// the file it was computed from.
+    pub async fn cache_hash(&self, photo_id: i64, hash: &str, size: u64, mtime: i64) -> Result<()> {
+        sqlx::query("UPDATE photos SET file_hash = ?, file_size = ?, file_mtime = ? WHERE id = ?")
+            .bind(hash)
+            .bind(size as i64)
+            .bind(mtime)
+            .bind(photo_id)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 }
+
+/// Joins photos with the same file hash, or perceptual hashes at most
+/// `max_distance` apart, into groups. A photo only joins a group when it is
+/// that close to every photo already in it, so two photos that are each
+/// similar to a third but not to each other do not end up together.
+fn group_duplicates(photos: Vec<(Photo, Option<u64>)>, max_distance: u32) -> Vec<DuplicateGroup> {
+    // Identical files first; they share a perceptual hash too.
+    let mut by_hash: HashMap<&str, usize> = HashMap::new();
+    let mut exact: Vec<(Option<u64>, Vec<usize>)> = Vec::new();
+    for (i, (photo, perceptual_hash)) in photos.iter().enumerate() {
+        match by_hash.get(photo.file_hash.as_str()) {
+            Some(&copies) => {
+                exact[copies].1.push(i);
+                exact[copies].0 = exact[copies].0.or(*perceptual_hash);
+            }
+            None => {
+                by_hash.insert(&photo.file_hash, exact.len());
+                exact.push((*perceptual_hash, vec![i]));
+            }
+        }
+    }
+
+    // Then each set of identical files joins the closest group all of whose
+    // members are similar to it, or starts a new one.
+    let mut components: Vec<(Vec<u64>, Vec<usize>)> = Vec::new();
+    let mut component_of: Vec<usize> = Vec::with_capacity(exact.len());
+    let mut tree = BkTree::default();
+    for (copies, (perceptual_hash, members)) in exact.into_iter().enumerate() {
+        let Some(hash) = perceptual_hash else {
+            component_of.push(components.len());
+            components.push((Vec::new(), members));
+            continue;
+        };
+        let mut candidates: Vec<usize> = tree.find(hash, max_distance).into_iter().map(|&c| component_of[c]).collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        let closest = candidates
+            .into_iter()
+            .filter_map(|component| {
+                let farthest = components[component].0.iter().map(|&other| similarity::distance(hash, other)).max()?;
+                (farthest <= max_distance).then_some((farthest, component))
+            })
+            .min();
+        let component = match closest {
+            Some((_, component)) => component,
+            None => {
+                components.push((Vec::new(), Vec::new()));
+                components.len() - 1
+            }
+        };
+        components[component].0.push(hash);
+        components[component].1.extend(members);
+        component_of.push(component);
+        tree.insert(hash, copies);
+    }
+
+    components
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            members.sort_by_key(|&i| {
+                let photo = &photos[i].0;
+                std::cmp::Reverse((photo.width as u64 * photo.height as u64, photo.file_size))
+            });
+            let (keeper, keeper_hash) = &photos[members[0]];
+            let exact = members.iter().all(|&i| photos[i].0.file_hash == keeper.file_hash);
+            let similarity = members
+                .iter()
+                .map(|&i| match (keeper_hash, photos[i].1) {
+                    _ if photos[i].0.file_hash == keeper.file_hash => 1.0,
+                    (Some(a), Some(b)) => similarity::similarity(*a, b),
+                    _ => 0.0,
+                })
+                .fold(1.0, f32::min);
+            DuplicateGroup {
+                hash: match keeper_hash {
+                    Some(hash) if !exact => format!("{:016x}", hash),
+                    _ => keeper.file_hash.clone(),
+                },
+                size: members.iter().skip(1).map(|&i| photos[i].0.file_size).sum(),
+                exact,
+                similarity,
+                photos: members.into_iter().map(|i| photos[i].0.clone()).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Keeps one photo of each group, chosen by `policy`, and deletes the rest
+/// through the sync engine so the backup drive follows. The keeper is first
+/// added to the albums and tags of every photo it replaces.
+pub async fn delete_duplicates(
+    sync_engine: &mut SyncEngine,
+    groups: &[Vec<i64>],
+    policy: &KeeperPolicy,
+) -> Result<DuplicateCleanup> {
+    let primary_root = sync_engine
+        .primary_root
+        .clone()
+        .ok_or(PhotoVaultError::DriveOffline { drive: DriveRole::Primary })?;
+    let mut cleanup = DuplicateCleanup::default();
+    for group in groups {
+        let mut photos = Vec::with_capacity(group.len());
+        let mut album_counts = Vec::with_capacity(group.len());
+        for &photo_id in group {
+            let photo = sqlx::query_as::<_, Photo>("SELECT * FROM photos WHERE id = ?")
+                .bind(photo_id)
+                .fetch_optional(&sync_engine.primary_db)
+                .await?;
+            match photo {
+                Some(photo) => {
+                    let albums: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photo_album WHERE photo_id = ?")
+                        .bind(photo_id)
+                        .fetch_one(&sync_engine.primary_db)
+                        .await?;
+                    photos.push(photo);
+                    album_counts.push(albums as usize);
+                }
+                None => cleanup.failed.push(PhotoOperationResult {
+                    photo_id,
+                    success: false,
+                    error: Some(PhotoVaultError::not_found(format!("Photo {}", photo_id))),
+                }),
+            }
+        }
+        if photos.len() < 2 {
+            continue;
+        }
+
+        let keeper = photos.swap_remove(choose_keeper(&photos, &album_counts, policy));
+        for photo in photos {
+            let freed = match library_path::resolve(&primary_root, &photo.path) {
+                Ok(path) => tokio::fs::metadata(path).await.map_or(0, |metadata| metadata.len()),
+                Err(_) => 0,
+            };
+            let merge = Operation::MergeLabels {
+                from: photo.path.clone(),
+                into: keeper.path.clone(),
+            };
+            let result = match sync_engine.execute_operation(merge).await {
+                Ok(()) => sync_engine.execute_operation(Operation::Delete { path: photo.path }).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {
+                    cleanup.bytes_freed += freed;
+                    cleanup.deleted.push(photo.id);
+                }
+                Err(e) => cleanup.failed.push(PhotoOperationResult {
+                    photo_id: photo.id,
+                    success: false,
+                    error: Some(e),
+                }),
+            }
+        }
+        cleanup.kept.push(keeper.id);
+    }
+    Ok(cleanup)
+}
+
+/// Index of the photo to keep. `album_counts[i]` is the number of albums
+/// `photos[i]` is in.
+fn choose_keeper(photos: &[Photo], album_counts: &[usize], policy: &KeeperPolicy) -> usize {
+    let resolution = |photo: &Photo| photo.width as u64 * photo.height as u64;
+    let preferred = |a: usize, b: usize| -> CmpOrdering {
+        let (x, y) = (&photos[a], &photos[b]);
+        let first = match policy {
+            KeeperPolicy::HighestResolution => resolution(x).cmp(&resolution(y)),
+            KeeperPolicy::LargestFile => x.file_size.cmp(&y.file_size),
+            // Photos without a date go last.
+            KeeperPolicy::OldestDateTaken => match (x.date_taken, y.date_taken) {
+                (Some(x), Some(y)) => y.cmp(&x),
+                (x, y) => x.is_some().cmp(&y.is_some()),
+            },
+            KeeperPolicy::PreferredFolder { folder } => {
+                let folder = folder.trim_matches('/');
+                let inside = |photo: &Photo| folder.is_empty() || photo.path.starts_with(&format!("{}/", folder));
+                inside(x).cmp(&inside(y))
+            }
+            KeeperPolicy::MostAlbums => album_counts[a].cmp(&album_counts[b]),
+        };
+        first
+            .then_with(|| resolution(x).cmp(&resolution(y)))
+            .then_with(|| x.file_size.cmp(&y.file_size))
+            .then_with(|| y.id.cmp(&x.id))
+    };
+    (0..photos.len()).max_by(|&a, &b| preferred(a, b)).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_find_duplicates_groups_identical_files_and_refreshes_stale_hashes() {
+        let (library, db_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = crate::db::init_db(&db_dir.path().join("test.db"), &migrations).await.unwrap();
+        let files = [("a.jpg", "same photo"), ("copy of a.jpg", "same photo"), ("b.jpg", "other")];
+        for (name, contents) in files {
+            std::fs::write(library.path().join(name), contents).unwrap();
+            // Stale hashes, as if the files changed since the last scan.
+            sqlx::query("INSERT INTO photos (path, filename, file_hash, file_size) VALUES (?, ?, 'stale', 0)")
+                .bind(name)
+                .bind(name)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let detector = DuplicateDetector::new(pool.clone(), library.path().to_path_buf());
+        let find =
+            || detector.find_duplicates(0.9, Arc::new(AtomicBool::new(false)), Arc::new(|_: &DuplicateProgress| {}));
+        let report = find().await.unwrap();
+        assert_eq!(report.groups.len(), 1);
+        let group = &report.groups[0];
+        let mut paths: Vec<&str> = group.photos.iter().map(|photo| photo.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a.jpg", "copy of a.jpg"]);
+        assert_eq!(group.size, "same photo".len() as u64);
+        assert_eq!(group.hash, crate::services::file_ops::hash_file_sync(&library.path().join("a.jpg")).unwrap());
+        // None of the files is an image, so none has a thumbnail to compare.
+        assert_eq!(report.photos_unsupported, 3);
+
+        // The second search uses the cached hashes.
+        let hashed = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let counter = hashed.clone();
+        let on_progress: DuplicateProgressCallback = Arc::new(move |progress: &DuplicateProgress| {
+            counter.store(progress.photos_hashed, Ordering::Relaxed);
+        });
+        detector.find_duplicates(0.9, Arc::new(AtomicBool::new(false)), on_progress).await.unwrap();
+        assert_eq!(hashed.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_resized_jpeg_copy_is_grouped_above_threshold() {
+        let (library, db_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = crate::db::init_db(&db_dir.path().join("test.db"), &migrations).await.unwrap();
+        let shot = image::RgbImage::from_fn(120, 80, |x, y| image::Rgb([(x * 2) as u8, (y * 3) as u8, 90]));
+        shot.save(library.path().join("shot.png")).unwrap();
+        image::imageops::resize(&shot, 60, 40, image::imageops::FilterType::Triangle)
+            .save(library.path().join("shot-whatsapp.jpg"))
+            .unwrap();
+        let service = crate::services::file_ops::FileOperationService::new(library.path().to_path_buf(), pool.clone());
+        for name in ["shot.png", "shot-whatsapp.jpg"] {
+            service.catalog_file(&library.path().join(name)).await.unwrap();
+        }
+
+        let detector = DuplicateDetector::new(pool, library.path().to_path_buf());
+        let find = |threshold| {
+            detector.find_duplicates(threshold, Arc::new(AtomicBool::new(false)), Arc::new(|_: &DuplicateProgress| {}))
+        };
+        let report = find(0.9).await.unwrap();
+        assert_eq!(report.groups.len(), 1);
+        let group = &report.groups[0];
+        assert!(!group.exact && group.similarity >= 0.9);
+        // The full-size original is the one to keep.
+        assert_eq!(group.photos[0].path, "shot.png");
+        assert_eq!(group.size, group.photos[1].file_size);
+    }
+
+    #[tokio::test]
+    async fn test_delete_duplicates_keeps_preferred_folder_and_merges_labels_on_both_drives() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let primary_db = crate::db::init_db(&db_dir.path().join("primary.db"), &migrations).await.unwrap();
+        let backup_db = crate::db::init_db(&db_dir.path().join("backup.db"), &migrations).await.unwrap();
+        for root in [primary.path(), backup.path()] {
+            for folder in ["Inbox", "2024"] {
+                std::fs::create_dir_all(root.join(folder)).unwrap();
+                std::fs::write(root.join(folder).join("a.jpg"), b"photo").unwrap();
+            }
+        }
+        for pool in [&primary_db, &backup_db] {
+            for statement in [
+                "INSERT INTO photos (id, path, filename, file_hash) VALUES (1, 'Inbox/a.jpg', 'a.jpg', 'hash')",
+                "INSERT INTO photos (id, path, filename, file_hash) VALUES (2, '2024/a.jpg', 'a.jpg', 'hash')",
+                "INSERT INTO albums (id, name) VALUES (1, 'Trip')",
+                "INSERT INTO tags (id, name) VALUES (1, 'beach')",
+                "INSERT INTO photo_album (photo_id, album_id) VALUES (1, 1)",
+                "INSERT INTO photo_tag (photo_id, tag_id) VALUES (1, 1)",
+            ] {
+                sqlx::query(statement).execute(pool).await.unwrap();
+            }
+        }
+
+        let mut engine = SyncEngine::new(
+            primary_db.clone(),
+            Some(backup_db.clone()),
+            Some(primary.path().to_path_buf()),
+            Some(backup.path().to_path_buf()),
+        );
+        let policy = KeeperPolicy::PreferredFolder { folder: "2024".into() };
+        let cleanup = delete_duplicates(&mut engine, &[vec![1, 2]], &policy).await.unwrap();
+
+        assert_eq!((cleanup.kept, cleanup.deleted), (vec![2], vec![1]));
+        assert_eq!(cleanup.bytes_freed, b"photo".len() as u64);
+        for (root, pool) in [(primary.path(), &primary_db), (backup.path(), &backup_db)] {
+            assert!(!root.join("Inbox").join("a.jpg").exists());
+            assert!(root.join("2024").join("a.jpg").exists());
+            let albums: Vec<i64> = sqlx::query_scalar("SELECT photo_id FROM photo_album").fetch_all(pool).await.unwrap();
+            let tags: Vec<i64> = sqlx::query_scalar("SELECT photo_id FROM photo_tag").fetch_all(pool).await.unwrap();
+            assert_eq!((albums, tags), (vec![2], vec![2]));
+        }
+    }
+
+    #[test]
+    fn test_photos_similar_only_through_a_third_are_not_grouped() {
+        let photo = |id: i64| Photo {
+            id,
+            path: format!("{}.jpg", id),
+            filename: format!("{}.jpg", id),
+            file_hash: id.to_string(),
+            file_size: 100,
+            date_taken: None,
+            width: 100,
+            height: 100,
+            format: "JPEG".into(),
+        };
+        // B is within the threshold of A and of C, but A and C are twice as
+        // far apart.
+        let max_distance = similarity::max_distance(0.9);
+        let steps = (1u64 << max_distance) - 1;
+        let (a, b, c) = (0, steps, steps | (steps << max_distance));
+        assert!(similarity::distance(a, c) > max_distance);
+
+        let photos = vec![(photo(1), Some(a)), (photo(2), Some(b)), (photo(3), Some(c))];
+        let groups = group_duplicates(photos, max_distance);
+        assert_eq!(groups.len(), 1);
+        let mut ids: Vec<i64> = groups[0].photos.iter().map(|photo| photo.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2]);
+        assert!(groups[0].similarity >= 0.9);
+    }
+}
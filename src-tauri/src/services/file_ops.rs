@@ -1,23 +1,571 @@
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io;
 use std::path::{Path, PathBuf};
-use crate::models::photo::Photo;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use crate::error::{PhotoVaultError, Result};
+use crate::models::photo::{Photo, PhotoMetadata};
+use crate::models::scan::{ScanError, ScanPhase, ScanProgress, ScanSummary};
+use crate::services::format;
+use crate::services::library_path;
+use crate::services::metadata;
+use log::debug;
+use log::warn;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use walkdir::{DirEntry, WalkDir};
+
+/// Number of photos written to the database per transaction during a scan.
+const SCAN_BATCH_SIZE: usize = 500;
+
+/// Minimum time between two progress callbacks.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub type ProgressCallback = Arc<dyn Fn(&ScanProgress) + Send + Sync>;
+
+/// Tracks scan progress and forwards it to the callback at a bounded rate.
+struct ProgressReporter {
+    callback: ProgressCallback,
+    progress: ScanProgress,
+    processing_started: Option<Instant>,
+    last_report: Option<Instant>,
+}
+
+impl ProgressReporter {
+    fn new(callback: ProgressCallback) -> Self {
+        Self {
+            callback,
+            progress: ScanProgress {
+                phase: ScanPhase::Discovering,
+                files_discovered: 0,
+                files_processed: 0,
+                current_path: None,
+                error_count: 0,
+                eta_seconds: None,
+            },
+            processing_started: None,
+            last_report: None,
+        }
+    }
+
+    fn start_processing(&mut self) {
+        self.progress.phase = ScanPhase::Processing;
+        self.processing_started = Some(Instant::now());
+        self.report(true);
+    }
+
+    fn finish(&mut self) {
+        self.progress.phase = ScanPhase::Finished;
+        self.progress.current_path = None;
+        self.progress.eta_seconds = Some(0);
+        self.report(true);
+    }
+
+    fn report(&mut self, force: bool) {
+        let now = Instant::now();
+        if !force && self.last_report.is_some_and(|last| now - last < PROGRESS_INTERVAL) {
+            return;
+        }
+        self.progress.eta_seconds = self.estimate_remaining();
+        self.last_report = Some(now);
+        (self.callback)(&self.progress);
+    }
+
+    fn estimate_remaining(&self) -> Option<u64> {
+        let started = self.processing_started?;
+        let processed = self.progress.files_processed;
+        if processed == 0 {
+            return None;
+        }
+        let remaining = self.progress.files_discovered.saturating_sub(processed);
+        let per_file = started.elapsed().as_secs_f64() / processed as f64;
+        Some((per_file * remaining as f64).ceil() as u64)
+    }
+}
+
+/// A file found on disk during a scan, before its contents are read.
+struct ScannedFile {
+    path: PathBuf,
+    size: u64,
+    mtime: i64,
+}
+
+/// The state of a catalogued file as recorded by the previous scan.
+struct KnownFile {
+    id: i64,
+    size: u64,
+    mtime: Option<i64>,
+}
 
 pub struct FileOperationService {
     primary_path: PathBuf,
+    pool: SqlitePool,
 }
 
 impl FileOperationService {
-    pub async fn scan_directory(&self, path: &Path) -> Result<Vec<Photo>, String> {
-        // Placeholder implementation
-        Ok(vec![])
+    pub fn new(primary_path: PathBuf, pool: SqlitePool) -> Self {
+        Self { primary_path, pool }
+    }
+
+    pub async fn scan_library(
+        &self,
+        cancel: Arc<AtomicBool>,
+        on_progress: ProgressCallback,
+    ) -> Result<ScanSummary> {
+        self.scan_directory(&self.primary_path, cancel, on_progress).await
+    }
+
+    /// Scans `path` and reconciles the catalog with it. Files whose size and
+    /// modification time match the last scan are not re-read. Setting `cancel`
+    /// stops the scan after the current file; everything processed so far is
+    /// committed.
+    pub async fn scan_directory(
+        &self,
+        path: &Path,
+        cancel: Arc<AtomicBool>,
+        on_progress: ProgressCallback,
+    ) -> Result<ScanSummary> {
+        let root = path.to_path_buf();
+        let folder = library_path::to_catalog_path(&self.primary_path, &root)
+            .ok_or_else(|| PhotoVaultError::invalid_input(format!("{} is outside the library", root.display())))?;
+        let mut known = self.load_known_files(&folder).await?;
+
+        let mut reporter = ProgressReporter::new(on_progress);
+        let walk_cancel = cancel.clone();
+        let (files, mut reporter) = tokio::task::spawn_blocking(move || {
+            let files = collect_files(&root, &walk_cancel, &mut reporter);
+            (files, reporter)
+        })
+        .await?;
+
+        let mut summary = ScanSummary {
+            cancelled: cancel.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        reporter.start_processing();
+
+        let mut batch = Vec::with_capacity(SCAN_BATCH_SIZE);
+        for file in &files {
+            if cancel.load(Ordering::Relaxed) {
+                summary.cancelled = true;
+                break;
+            }
+            reporter.progress.current_path = Some(file.path.to_string_lossy().into_owned());
+
+            let Some(key) = library_path::to_catalog_path(&self.primary_path, &file.path) else {
+                continue;
+            };
+            let previous = known.remove(&key);
+            if previous.as_ref().is_some_and(|p| p.size == file.size && p.mtime == Some(file.mtime)) {
+                summary.unchanged += 1;
+            } else {
+                let path = file.path.clone();
+                let read = tokio::task::spawn_blocking(move || read_photo(&path))
+                    .await?;
+                match read {
+                    Ok(Some(mut metadata)) => {
+                        metadata.photo.path = key;
+                        batch.push((metadata, file.mtime, previous.is_some()));
+                    }
+                    Ok(None) => debug!("Ignoring non-image file {}", file.path.display()),
+                    Err(e) => {
+                        warn!("Skipping {}: {}", file.path.display(), e);
+                        reporter.progress.error_count += 1;
+                        summary.errors.push(ScanError {
+                            path: key,
+                            message: e.to_string(),
+                        });
+                    }
+                }
+            }
+
+            reporter.progress.files_processed += 1;
+            reporter.report(false);
+            if batch.len() >= SCAN_BATCH_SIZE {
+                self.commit_batch(std::mem::take(&mut batch), &mut summary).await?;
+            }
+        }
+        self.commit_batch(batch, &mut summary).await?;
+
+        // Anything left in `known` was catalogued under this root but no longer
+        // exists. A cancelled scan has not seen every file, so skip this step.
+        if !summary.cancelled {
+            let removed: Vec<(i64, String)> =
+                known.into_iter().map(|(path, file)| (file.id, path)).collect();
+            self.remove_photos(&removed).await?;
+            summary.removed = removed.into_iter().map(|(_, path)| path).collect();
+        }
+
+        reporter.finish();
+        Ok(summary)
+    }
+
+    async fn commit_batch(
+        &self,
+        batch: Vec<(PhotoMetadata, i64, bool)>,
+        summary: &mut ScanSummary,
+    ) -> Result<()> {
+        for (photo, is_update) in self.upsert_photos(batch).await? {
+            if is_update {
+                summary.updated.push(photo);
+            } else {
+                summary.added.push(photo);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn read_metadata(&self, path: &Path) -> Result<PhotoMetadata> {
+        let file = path.to_path_buf();
+        tokio::task::spawn_blocking(move || read_photo(&file))
+            .await??
+            .ok_or_else(|| PhotoVaultError::UnsupportedFormat {
+                message: format!("{} is not a supported image", path.display()),
+            })
+    }
+
+    /// Reads a single file under the library root and adds or updates its
+    /// catalog row.
+    pub async fn catalog_file(&self, path: &Path) -> Result<Photo> {
+        let key = library_path::to_catalog_path(&self.primary_path, path)
+            .ok_or_else(|| PhotoVaultError::invalid_input(format!("{} is outside the library", path.display())))?;
+        let mut metadata = self.read_metadata(path).await?;
+        metadata.photo.path = key;
+        self.save_photo(metadata).await
+    }
+
+    /// Adds or updates the catalog row of a file under the library root,
+    /// keyed by `metadata.photo.path`, from data that was already read.
+    pub async fn save_photo(&self, metadata: PhotoMetadata) -> Result<Photo> {
+        let path = library_path::resolve(&self.primary_path, &metadata.photo.path)?;
+        let file_metadata = tokio::fs::metadata(&path).await.map_err(|e| PhotoVaultError::io(&path, e))?;
+        let saved = self
+            .upsert_photos(vec![(metadata, modified_millis(&file_metadata), false)])
+            .await?;
+        saved
+            .into_iter()
+            .next()
+            .map(|(photo, _)| photo)
+            .ok_or_else(|| PhotoVaultError::internal("Photo was not saved"))
+    }
+
+    /// Loads the catalogued files under `folder`, a catalog path.
+    async fn load_known_files(&self, folder: &str) -> Result<HashMap<String, KnownFile>> {
+        let rows: Vec<(i64, String, Option<i64>, Option<i64>)> =
+            sqlx::query_as("SELECT id, path, file_size, file_mtime FROM photos")
+                .fetch_all(&self.pool)
+                .await?;
+        let prefix = format!("{}/", folder);
+        Ok(rows
+            .into_iter()
+            .filter(|(_, path, _, _)| folder.is_empty() || path.starts_with(&prefix))
+            .map(|(id, path, size, mtime)| {
+                let size = size.unwrap_or(0) as u64;
+                (path, KnownFile { id, size, mtime })
+            })
+            .collect())
+    }
+
+    async fn upsert_photos(
+        &self,
+        photos: Vec<(PhotoMetadata, i64, bool)>,
+    ) -> Result<Vec<(Photo, bool)>> {
+        let mut tx = self.pool.begin().await?;
+        let mut saved = Vec::with_capacity(photos.len());
+        for (PhotoMetadata { mut photo, exif }, mtime, is_update) in photos {
+            photo.id = sqlx::query_scalar(
+                "INSERT INTO photos (path, filename, file_hash, file_size, date_taken, width, height, format, file_mtime, last_scanned_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+                 ON CONFLICT(path) DO UPDATE SET
+                    filename = excluded.filename,
+                    file_hash = excluded.file_hash,
+                    file_size = excluded.file_size,
+                    date_taken = excluded.date_taken,
+                    width = excluded.width,
+                    height = excluded.height,
+                    format = excluded.format,
+                    file_mtime = excluded.file_mtime,
+                    last_scanned_at = excluded.last_scanned_at
+                 RETURNING id",
+            )
+            .bind(&photo.path)
+            .bind(&photo.filename)
+            .bind(&photo.file_hash)
+            .bind(photo.file_size as i64)
+            .bind(photo.date_taken)
+            .bind(photo.width)
+            .bind(photo.height)
+            .bind(&photo.format)
+            .bind(mtime)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            sqlx::query("DELETE FROM photo_exif WHERE photo_id = ?")
+                .bind(photo.id)
+                .execute(&mut *tx)
+                .await?;
+            if let Some(exif) = exif {
+                sqlx::query(
+                    "INSERT INTO photo_exif (photo_id, date_time_original, offset_time_original, camera_make, camera_model,
+                        lens_model, iso, aperture, exposure_time, focal_length, orientation, gps_latitude, gps_longitude, gps_altitude)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(photo.id)
+                .bind(&exif.date_time_original)
+                .bind(&exif.offset_time_original)
+                .bind(&exif.camera_make)
+                .bind(&exif.camera_model)
+                .bind(&exif.lens_model)
+                .bind(exif.iso)
+                .bind(exif.aperture)
+                .bind(exif.exposure_time)
+                .bind(exif.focal_length)
+                .bind(exif.orientation)
+                .bind(exif.gps_latitude)
+                .bind(exif.gps_longitude)
+                .bind(exif.gps_altitude)
+                .execute(&mut *tx)
+                .await?;
+            }
+            saved.push((photo, is_update));
+        }
+        tx.commit().await?;
+        Ok(saved)
+    }
+
+    async fn remove_photos(&self, photos: &[(i64, String)]) -> Result<()> {
+        for chunk in photos.chunks(SCAN_BATCH_SIZE) {
+            let mut tx = self.pool.begin().await?;
+            for (id, _) in chunk {
+                sqlx::query("DELETE FROM photo_exif WHERE photo_id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM photo_album WHERE photo_id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM photo_tag WHERE photo_id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM photo_verifications WHERE photo_id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM photos WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the SHA-256 of a file without loading it into memory.
+pub fn hash_file_sync(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// A file's modification time as stored in `photos.file_mtime`.
+pub fn modified_millis(metadata: &Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_millis() as i64)
+}
+
+/// Hidden entries, such as the `.photovault` folder, are not part of the
+/// library.
+pub fn is_hidden(entry: &DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+fn collect_files(root: &Path, cancel: &AtomicBool, reporter: &mut ProgressReporter) -> Vec<ScannedFile> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root).into_iter().filter_entry(|e| !is_hidden(e)) {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Failed to read directory entry: {}", e);
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("Failed to stat {}: {}", entry.path().display(), e);
+                continue;
+            }
+        };
+
+        reporter.progress.files_discovered += 1;
+        reporter.progress.current_path = Some(entry.path().to_string_lossy().into_owned());
+        reporter.report(false);
+        files.push(ScannedFile {
+            size: metadata.len(),
+            mtime: modified_millis(&metadata),
+            path: entry.into_path(),
+        });
+    }
+    files
+}
+
+/// Reads a photo's catalog data. Returns `Ok(None)` when the file's contents
+/// are not a supported image, whatever its extension.
+fn read_photo(path: &Path) -> Result<Option<PhotoMetadata>> {
+    let Some(format) = format::sniff_file(path).map_err(|e| PhotoVaultError::io(path, e))? else {
+        return Ok(None);
+    };
+    let file_metadata = std::fs::metadata(path).map_err(|e| PhotoVaultError::io(path, e))?;
+    let (width, height) = format::read_dimensions(path, format)?;
+    let file_hash = hash_file_sync(path).map_err(|e| PhotoVaultError::io(path, e))?;
+    let exif = metadata::read_exif(path, format);
+
+    let photo = Photo {
+        id: 0,
+        path: path.to_string_lossy().into_owned(),
+        filename: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        file_hash,
+        file_size: file_metadata.len(),
+        date_taken: exif.as_ref().and_then(metadata::date_taken_utc),
+        width,
+        height,
+        format: format.name().to_string(),
+    };
+    Ok(Some(PhotoMetadata { photo, exif }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn test_pool(dir: &Path) -> SqlitePool {
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        crate::db::init_db(&dir.join("test.db"), &migrations).await.unwrap()
     }
 
-    pub fn get_supported_formats() -> Vec<&'static str> {
-        // Placeholder implementation
-        vec!["jpg", "jpeg", "png", "gif"]
+    #[tokio::test]
+    async fn test_scan_directory_finds_and_upserts_images() {
+        let library = tempdir().unwrap();
+        let nested = library.path().join("2024").join("trip");
+        std::fs::create_dir_all(&nested).unwrap();
+        image::RgbImage::new(4, 3).save(nested.join("a.png")).unwrap();
+        image::RgbImage::new(2, 2).save(library.path().join("b.JPG")).unwrap();
+        std::fs::write(library.path().join("notes.txt"), "not a photo").unwrap();
+        // Misnamed files are catalogued by their contents.
+        std::fs::copy(nested.join("a.png"), library.path().join("fake.jpg")).unwrap();
+
+        let db_dir = tempdir().unwrap();
+        let pool = test_pool(db_dir.path()).await;
+        let service = FileOperationService::new(library.path().to_path_buf(), pool.clone());
+        let scan = || service.scan_library(Arc::new(AtomicBool::new(false)), Arc::new(|_: &ScanProgress| {}));
+
+        let summary = scan().await.unwrap();
+        let mut photos = summary.added;
+        photos.sort_by(|a, b| a.filename.cmp(&b.filename));
+        assert_eq!(photos.len(), 3);
+        assert_eq!((photos[0].width, photos[0].height), (4, 3));
+        assert_eq!(photos[0].format, "PNG");
+        assert_eq!(photos[0].path, "2024/trip/a.png");
+        assert_eq!(photos[1].format, "JPEG");
+        assert_eq!(photos[2].format, "PNG");
+        assert_eq!(photos[0].file_hash.len(), 64);
+
+        std::fs::remove_file(library.path().join("b.JPG")).unwrap();
+        image::RgbImage::new(8, 8).save(nested.join("a.png")).unwrap();
+        image::RgbImage::new(1, 1).save(library.path().join("c.gif")).unwrap();
+
+        let summary = scan().await.unwrap();
+        assert_eq!(summary.added.len(), 1);
+        assert_eq!(summary.updated.len(), 1);
+        assert_eq!(summary.updated[0].width, 8);
+        assert_eq!(summary.removed.len(), 1);
+        assert_eq!(summary.removed, vec!["b.JPG".to_string()]);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
     }
 
-    pub async fn read_metadata(&self, path: &Path) -> Result<Photo, String> {
-        // Placeholder implementation
-        Err("Not implemented".to_string())
+    #[tokio::test]
+    async fn test_rescan_of_unchanged_library_skips_every_file() {
+        let (library, db_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        image::RgbImage::new(4, 3).save(library.path().join("a.png")).unwrap();
+        image::RgbImage::new(2, 2).save(library.path().join("b.jpg")).unwrap();
+        let service = FileOperationService::new(library.path().to_path_buf(), test_pool(db_dir.path()).await);
+        let scan = || service.scan_library(Arc::new(AtomicBool::new(false)), Arc::new(|_: &ScanProgress| {}));
+        assert_eq!(scan().await.unwrap().added.len(), 2);
+
+        let summary = scan().await.unwrap();
+        assert_eq!(summary.unchanged, 2);
+        assert!(summary.added.is_empty() && summary.updated.is_empty() && summary.removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rescan_removes_catalog_rows_of_deleted_files() {
+        let (library, db_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        image::RgbImage::new(4, 3).save(library.path().join("a.png")).unwrap();
+        image::RgbImage::new(2, 2).save(library.path().join("b.jpg")).unwrap();
+        let pool = test_pool(db_dir.path()).await;
+        let service = FileOperationService::new(library.path().to_path_buf(), pool.clone());
+        let scan = || service.scan_library(Arc::new(AtomicBool::new(false)), Arc::new(|_: &ScanProgress| {}));
+        scan().await.unwrap();
+        std::fs::remove_file(library.path().join("b.jpg")).unwrap();
+
+        let summary = scan().await.unwrap();
+        assert_eq!(summary.removed, vec!["b.jpg".to_string()]);
+        assert_eq!(summary.unchanged, 1);
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos WHERE path = 'b.jpg'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_scan_keeps_catalog_rows() {
+        let library = tempdir().unwrap();
+        image::RgbImage::new(2, 2).save(library.path().join("a.png")).unwrap();
+
+        let db_dir = tempdir().unwrap();
+        let pool = test_pool(db_dir.path()).await;
+        let service = FileOperationService::new(library.path().to_path_buf(), pool.clone());
+        service
+            .scan_library(Arc::new(AtomicBool::new(false)), Arc::new(|_: &ScanProgress| {}))
+            .await
+            .unwrap();
+
+        let summary = service
+            .scan_library(Arc::new(AtomicBool::new(true)), Arc::new(|_: &ScanProgress| {}))
+            .await
+            .unwrap();
+        assert!(summary.cancelled);
+        assert!(summary.removed.is_empty());
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
     }
 }
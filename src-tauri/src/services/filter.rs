@@ -1,6 +1,11 @@
-use crate::models::filter::FilterCriteria;
+use crate::error::{PhotoVaultError, Result};
+use crate::models::filter::{FilterCriteria, PhotoPage, PhotoSort, TagMatch};
 use crate::models::photo::Photo;
-use sqlx::SqlitePool;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+/// Photos per page when the criteria do not say.
+const DEFAULT_PAGE_SIZE: u32 = 200;
+const MAX_PAGE_SIZE: u32 = 1000;
 
 pub struct FilterService {
     pool: SqlitePool,
@@ -11,8 +16,198 @@ impl FilterService {
         Self { pool }
     }
 
-    pub async fn filter_photos(&self, criteria: FilterCriteria) -> Result<Vec<Photo>, String> {
-        // Logic to filter photos based on criteria
-        Ok(vec![])
+    /// One page of the photos matching every given criterion. Pages are
+    /// found by seeking past the last photo of the previous one, so deep
+    /// pages cost the same as the first.
+    pub async fn filter_photos(&self, criteria: FilterCriteria) -> Result<PhotoPage> {
+        let limit = criteria.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        if let Some(after) = criteria.after {
+            let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM photos WHERE id = ?")
+                .bind(after)
+                .fetch_optional(&self.pool)
+                .await?;
+            if exists.is_none() {
+                return Err(PhotoVaultError::not_found(format!("Photo {}", after)));
+            }
+        }
+
+        let mut query = build_query(&criteria, limit);
+        let mut photos = query.build_query_as::<Photo>().fetch_all(&self.pool).await?;
+        let next_cursor = if photos.len() > limit as usize {
+            photos.truncate(limit as usize);
+            photos.last().map(|photo| photo.id)
+        } else {
+            None
+        };
+        Ok(PhotoPage { photos, next_cursor })
+    }
+}
+
+/// Expression photos are ordered by, before their id. These match the
+/// expression indexes, so they must not change without them.
+fn sort_key(sort: PhotoSort) -> &'static str {
+    match sort {
+        PhotoSort::DateTaken => "COALESCE(date_taken, '')",
+        PhotoSort::DateAdded => "id",
+        PhotoSort::Filename => "filename",
+        PhotoSort::FileSize => "COALESCE(file_size, 0)",
+    }
+}
+
+/// Selects one photo more than `limit`, to tell whether there is a next page.
+fn build_query(criteria: &FilterCriteria, limit: u32) -> QueryBuilder<'_, Sqlite> {
+    let mut query = QueryBuilder::new("SELECT * FROM photos WHERE 1 = 1");
+    if let Some(from) = criteria.date_from {
+        query.push(" AND date_taken >= ").push_bind(from);
+    }
+    if let Some(to) = criteria.date_to {
+        query.push(" AND date_taken <= ").push_bind(to);
+    }
+    if let Some(width) = criteria.min_width {
+        query.push(" AND width >= ").push_bind(width);
+    }
+    if let Some(height) = criteria.min_height {
+        query.push(" AND height >= ").push_bind(height);
+    }
+
+    if let Some(tags) = criteria.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        let mut tags = tags.clone();
+        tags.sort_unstable();
+        tags.dedup();
+        query.push(" AND id IN (SELECT photo_id FROM photo_tag WHERE tag_id IN (");
+        push_ids(&mut query, &tags);
+        query.push(")");
+        if criteria.tag_match == TagMatch::All {
+            query.push(" GROUP BY photo_id HAVING COUNT(*) = ").push_bind(tags.len() as i64);
+        }
+        query.push(")");
+    }
+    if let Some(albums) = criteria.albums.as_ref().filter(|albums| !albums.is_empty()) {
+        query.push(" AND id IN (SELECT photo_id FROM photo_album WHERE album_id IN (");
+        push_ids(&mut query, albums);
+        query.push("))");
+    }
+
+    for word in criteria.query.iter().flat_map(|text| text.split_whitespace()) {
+        let pattern = format!("%{}%", escape_like(word));
+        query
+            .push(" AND (path LIKE ")
+            .push_bind(pattern.clone())
+            .push(
+                " ESCAPE '\\' OR id IN (SELECT photo_tag.photo_id FROM photo_tag \
+                 JOIN tags ON tags.id = photo_tag.tag_id WHERE tags.name LIKE ",
+            )
+            .push_bind(pattern)
+            .push(" ESCAPE '\\'))");
     }
-}
\ No newline at end of file
+
+    let key = sort_key(criteria.sort);
+    let (direction, past) = if criteria.descending { ("DESC", "<") } else { ("ASC", ">") };
+    if let Some(after) = criteria.after {
+        // The first bound is implied by the second, but SQLite only seeks an
+        // expression index on a plain comparison.
+        let after_key = format!("(SELECT {} FROM photos WHERE id = ", key);
+        query
+            .push(format!(" AND {} {}= {}", key, past, after_key))
+            .push_bind(after)
+            .push(format!(") AND ({}, id) {} ({}", key, past, after_key))
+            .push_bind(after)
+            .push("), ")
+            .push_bind(after)
+            .push(")");
+    }
+    query
+        .push(format!(" ORDER BY {} {}, id {} LIMIT ", key, direction, direction))
+        .push_bind(limit as i64 + 1);
+    query
+}
+
+fn push_ids(query: &mut QueryBuilder<'_, Sqlite>, ids: &[i64]) {
+    let mut separated = query.separated(", ");
+    for &id in ids {
+        separated.push_bind(id);
+    }
+}
+
+/// Makes `%`, `_` and `\` match themselves in a LIKE pattern escaped by `\`.
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_filter_photos_combines_criteria_and_pages_by_keyset() {
+        let db_dir = tempdir().unwrap();
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = crate::db::init_db(&db_dir.path().join("test.db"), &migrations).await.unwrap();
+        let photos = [
+            (1, "2023/beach_1.jpg", Some("2023-07-01T10:00:00Z"), 4000),
+            (2, "2023/beach_2.jpg", Some("2023-07-02T10:00:00Z"), 800),
+            (3, "2024/city.jpg", Some("2024-03-01T10:00:00Z"), 4000),
+            (4, "scans/100%.jpg", None, 4000),
+        ];
+        for (id, path, date_taken, width) in photos {
+            let date_taken = date_taken.map(|date| date.parse::<chrono::DateTime<chrono::Utc>>().unwrap());
+            sqlx::query(
+                "INSERT INTO photos (id, path, filename, file_hash, date_taken, width, height)
+                 VALUES (?, ?, ?, 'hash', ?, ?, 3000)",
+            )
+            .bind(id)
+            .bind(path)
+            .bind(path.rsplit('/').next().unwrap())
+            .bind(date_taken)
+            .bind(width)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        for statement in [
+            "INSERT INTO tags (id, name) VALUES (1, 'summer'), (2, 'family')",
+            "INSERT INTO photo_tag (photo_id, tag_id) VALUES (1, 1), (1, 2), (2, 1), (3, 2)",
+            "INSERT INTO albums (id, name) VALUES (1, 'Holidays')",
+            "INSERT INTO photo_album (photo_id, album_id) VALUES (2, 1), (3, 1)",
+        ] {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+
+        let service = FilterService::new(pool);
+        let ids = |criteria: FilterCriteria| {
+            let service = &service;
+            async move {
+                let page = service.filter_photos(criteria).await.unwrap();
+                (page.photos.iter().map(|photo| photo.id).collect::<Vec<_>>(), page.next_cursor)
+            }
+        };
+        let tags = Some(vec![1, 2]);
+        assert_eq!(ids(FilterCriteria { tags: tags.clone(), ..Default::default() }).await.0, vec![1, 2, 3]);
+        let all = FilterCriteria { tags, tag_match: TagMatch::All, ..Default::default() };
+        assert_eq!(ids(all).await.0, vec![1]);
+        let albums = FilterCriteria { albums: Some(vec![1]), min_width: Some(1000), ..Default::default() };
+        assert_eq!(ids(albums).await.0, vec![3]);
+        let since = FilterCriteria { date_from: Some("2023-07-02T00:00:00Z".parse().unwrap()), ..Default::default() };
+        assert_eq!(ids(since).await.0, vec![2, 3]);
+        // Words match the path or a tag, and `%` is not a wildcard.
+        let family = FilterCriteria { query: Some("FAMILY 2023".into()), ..Default::default() };
+        assert_eq!(ids(family).await.0, vec![1]);
+        assert_eq!(ids(FilterCriteria { query: Some("100%".into()), ..Default::default() }).await.0, vec![4]);
+        assert!(ids(FilterCriteria { query: Some("1%".into()), ..Default::default() }).await.0.is_empty());
+
+        // Newest first, two at a time; the undated scan comes last.
+        let mut criteria = FilterCriteria { descending: true, limit: Some(2), ..Default::default() };
+        assert_eq!(ids(criteria.clone()).await, (vec![3, 2], Some(2)));
+        criteria.after = Some(2);
+        assert_eq!(ids(criteria).await, (vec![1, 4], None));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::error::{PhotoVaultError, Result};
+use image::{DynamicImage, ImageFormat};
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes read from the start of a file to identify its format. Large enough
+/// to reach the first IFD of TIFF-based RAW files and the `ftyp` box of HEIF.
+const SNIFF_LEN: u64 = 64 * 1024;
+
+/// Upper bound on the bytes searched for HEIF `ispe` boxes.
+const HEIF_HEADER_LEN: u64 = 1024 * 1024;
+
+/// Upper bound on the bytes of a RAW file searched for embedded JPEG
+/// previews. Cameras write their previews ahead of the sensor data.
+const PREVIEW_SEARCH_LEN: u64 = 16 * 1024 * 1024;
+
+const TIFF_TAG_MAKE: u16 = 0x010F;
+const TIFF_TAG_DNG_VERSION: u16 = 0xC612;
+const TIFF_TYPE_ASCII: u16 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhotoFormat {
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+    Tiff,
+    Heic,
+    Avif,
+    Cr2,
+    Cr3,
+    Nef,
+    Arw,
+    Dng,
+}
+
+impl PhotoFormat {
+    /// The value stored in `photos.format`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PhotoFormat::Jpeg => "JPEG",
+            PhotoFormat::Png => "PNG",
+            PhotoFormat::Gif => "GIF",
+            PhotoFormat::WebP => "WEBP",
+            PhotoFormat::Tiff => "TIFF",
+            PhotoFormat::Heic => "HEIC",
+            PhotoFormat::Avif => "AVIF",
+            PhotoFormat::Cr2 => "CR2",
+            PhotoFormat::Cr3 => "CR3",
+            PhotoFormat::Nef => "NEF",
+            PhotoFormat::Arw => "ARW",
+            PhotoFormat::Dng => "DNG",
+        }
+    }
+
+    pub fn is_heif(self) -> bool {
+        matches!(self, PhotoFormat::Heic | PhotoFormat::Avif)
+    }
+
+    /// The decoder the `image` crate can use directly, if any.
+    pub fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            PhotoFormat::Jpeg => Some(ImageFormat::Jpeg),
+            PhotoFormat::Png => Some(ImageFormat::Png),
+            PhotoFormat::Gif => Some(ImageFormat::Gif),
+            PhotoFormat::WebP => Some(ImageFormat::WebP),
+            PhotoFormat::Tiff => Some(ImageFormat::Tiff),
+            _ => None,
+        }
+    }
+}
+
+/// Identifies an image format from the leading bytes of a file.
+pub fn detect_format(header: &[u8]) -> Option<PhotoFormat> {
+    if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some(PhotoFormat::Jpeg);
+    }
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(PhotoFormat::Png);
+    }
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        return Some(PhotoFormat::Gif);
+    }
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        return Some(PhotoFormat::WebP);
+    }
+    if header.len() >= 12 && &header[4..8] == b"ftyp" {
+        return detect_isobmff(header);
+    }
+    if header.starts_with(b"II*\0") || header.starts_with(b"MM\0*") {
+        return Some(detect_tiff(header));
+    }
+    None
+}
+
+/// Reads the start of `path` and identifies its format.
+pub fn sniff_file(path: &Path) -> io::Result<Option<PhotoFormat>> {
+    let mut header = Vec::new();
+    File::open(path)?.take(SNIFF_LEN).read_to_end(&mut header)?;
+    Ok(detect_format(&header))
+}
+
+fn detect_isobmff(header: &[u8]) -> Option<PhotoFormat> {
+    let box_len = (u32::from_be_bytes(header[0..4].try_into().ok()?) as usize).min(header.len());
+    let major = &header[8..12];
+    // Compatible brands follow the major brand and minor version.
+    let brands = header.get(16..box_len).unwrap_or_default();
+    let has_brand = |brand: &[u8]| major == brand || brands.chunks_exact(4).any(|b| b == brand);
+
+    if has_brand(b"crx ") {
+        Some(PhotoFormat::Cr3)
+    } else if has_brand(b"avif") || has_brand(b"avis") {
+        Some(PhotoFormat::Avif)
+    } else if [b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1"]
+        .iter()
+        .any(|brand| has_brand(*brand))
+    {
+        Some(PhotoFormat::Heic)
+    } else {
+        None
+    }
+}
+
+/// Tells plain TIFF apart from the TIFF-based RAW formats by looking at the
+/// first IFD.
+fn detect_tiff(header: &[u8]) -> PhotoFormat {
+    if header.get(8..10) == Some(b"CR") {
+        return PhotoFormat::Cr2;
+    }
+
+    let little_endian = header.starts_with(b"II");
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes: [u8; 2] = header.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes: [u8; 4] = header.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+
+    let Some(ifd) = read_u32(4).map(|offset| offset as usize) else {
+        return PhotoFormat::Tiff;
+    };
+    let entries = read_u16(ifd).unwrap_or(0) as usize;
+    let mut make = String::new();
+    for index in 0..entries {
+        let entry = ifd + 2 + index * 12;
+        let (Some(tag), Some(field_type), Some(count)) =
+            (read_u16(entry), read_u16(entry + 2), read_u32(entry + 4))
+        else {
+            break;
+        };
+        if tag == TIFF_TAG_DNG_VERSION {
+            return PhotoFormat::Dng;
+        }
+        if tag == TIFF_TAG_MAKE && field_type == TIFF_TYPE_ASCII {
+            let count = count as usize;
+            let start = if count <= 4 {
+                entry + 8
+            } else {
+                read_u32(entry + 8).unwrap_or(0) as usize
+            };
+            if let Some(bytes) = header.get(start..start + count) {
+                make = String::from_utf8_lossy(bytes).to_uppercase();
+            }
+        }
+    }
+
+    if make.starts_with("NIKON") {
+        PhotoFormat::Nef
+    } else if make.starts_with("SONY") {
+        PhotoFormat::Arw
+    } else {
+        PhotoFormat::Tiff
+    }
+}
+
+/// Reads the pixel dimensions of an image. RAW files report the size of their
+/// largest embedded JPEG preview.
+pub fn read_dimensions(path: &Path, format: PhotoFormat) -> Result<(u32, u32)> {
+    if let Some(image_format) = format.image_format() {
+        let mut reader = image::io::Reader::open(path).map_err(|e| PhotoVaultError::io(path, e))?;
+        reader.set_format(image_format);
+        return Ok(reader.into_dimensions()?);
+    }
+
+    if format.is_heif() {
+        let mut header = Vec::new();
+        File::open(path)
+            .and_then(|file| file.take(HEIF_HEADER_LEN).read_to_end(&mut header))
+            .map_err(|e| PhotoVaultError::io(path, e))?;
+        return heif_dimensions(&header).ok_or_else(|| PhotoVaultError::UnsupportedFormat {
+            message: "No image size found in HEIF file".to_string(),
+        });
+    }
+
+    find_embedded_preview(path)?
+        .map(|(_, dimensions)| dimensions)
+        .ok_or_else(|| PhotoVaultError::UnsupportedFormat {
+            message: "No embedded preview found in RAW file".to_string(),
+        })
+}
+
+/// Returns the largest `ispe` (image spatial extents) property, which belongs
+/// to the primary image rather than its thumbnails.
+fn heif_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    find_boxes(data, b"ispe")
+        .filter_map(|body| {
+            // Full box header: version (1 byte) and flags (3 bytes).
+            let width = u32::from_be_bytes(body.get(4..8)?.try_into().ok()?);
+            let height = u32::from_be_bytes(body.get(8..12)?.try_into().ok()?);
+            Some((width, height))
+        })
+        .max_by_key(|(width, height)| *width as u64 * *height as u64)
+}
+
+/// Finds ISOBMFF boxes of the given type anywhere in `data` and yields their
+/// bodies. Scanning for the type code avoids walking every container level.
+pub fn find_boxes<'a>(data: &'a [u8], box_type: &'a [u8; 4]) -> impl Iterator<Item = &'a [u8]> + 'a {
+    data.windows(4)
+        .enumerate()
+        .filter(move |(_, window)| window == box_type)
+        .filter_map(move |(index, _)| {
+            let start = index.checked_sub(4)?;
+            let size = u32::from_be_bytes(data[start..index].try_into().ok()?) as usize;
+            let body = data.get(index + 4..start.checked_add(size)?)?;
+            (size >= 8).then_some(body)
+        })
+}
+
+/// Locates the largest embedded JPEG in the first `PREVIEW_SEARCH_LEN`
+/// bytes of a RAW file and returns its offset and dimensions.
+pub fn find_embedded_preview(path: &Path) -> Result<Option<(u64, (u32, u32))>> {
+    let mut data = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(PREVIEW_SEARCH_LEN).read_to_end(&mut data))
+        .map_err(|e| PhotoVaultError::io(path, e))?;
+    Ok(embedded_preview(&data).map(|(offset, dimensions)| (offset as u64, dimensions)))
+}
+
+/// Decodes the JPEG that starts `offset` bytes into `path`, reading no
+/// further than its end-of-image marker.
+pub fn decode_jpeg_at(path: &Path, offset: u64) -> Result<DynamicImage> {
+    let mut file = File::open(path).map_err(|e| PhotoVaultError::io(path, e))?;
+    file.seek(SeekFrom::Start(offset)).map_err(|e| PhotoVaultError::io(path, e))?;
+    Ok(image::io::Reader::with_format(BufReader::new(file), ImageFormat::Jpeg).decode()?)
+}
+
+/// Finds the largest JPEG embedded in `data` and returns its offset and
+/// dimensions. Only the JPEG headers are read.
+fn embedded_preview(data: &[u8]) -> Option<(usize, (u32, u32))> {
+    data.windows(4)
+        .enumerate()
+        .filter(|(_, window)| window[0..3] == [0xFF, 0xD8, 0xFF] && window[3] >= 0xC0)
+        .filter_map(|(offset, _)| {
+            let dimensions = image::io::Reader::with_format(Cursor::new(&data[offset..]), ImageFormat::Jpeg)
+                .into_dimensions()
+                .ok()?;
+            Some((offset, dimensions))
+        })
+        .max_by_key(|(_, (width, height))| *width as u64 * *height as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    fn tiff_with_make(make: &[u8]) -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&TIFF_TAG_MAKE.to_le_bytes());
+        data.extend_from_slice(&TIFF_TYPE_ASCII.to_le_bytes());
+        data.extend_from_slice(&(make.len() as u32).to_le_bytes());
+        data.extend_from_slice(&26u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(make);
+        data
+    }
+
+    fn ftyp(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+        let size = 16 + 4 * compatible.len() as u32;
+        let mut data = size.to_be_bytes().to_vec();
+        data.extend_from_slice(b"ftyp");
+        data.extend_from_slice(major);
+        data.extend_from_slice(&0u32.to_be_bytes());
+        for brand in compatible {
+            data.extend_from_slice(*brand);
+        }
+        data
+    }
+
+    #[test]
+    fn test_detect_format_uses_content_not_extension() {
+        assert_eq!(detect_format(&[0xFF, 0xD8, 0xFF, 0xE1]), Some(PhotoFormat::Jpeg));
+        assert_eq!(detect_format(b"RIFF\0\0\0\0WEBPVP8 "), Some(PhotoFormat::WebP));
+        assert_eq!(detect_format(&ftyp(b"heic", &[b"mif1", b"heic"])), Some(PhotoFormat::Heic));
+        assert_eq!(detect_format(&ftyp(b"avif", &[b"mif1", b"avif"])), Some(PhotoFormat::Avif));
+        assert_eq!(detect_format(&ftyp(b"crx ", &[b"crx ", b"isom"])), Some(PhotoFormat::Cr3));
+        assert_eq!(detect_format(&ftyp(b"isom", &[b"mp42"])), None);
+        assert_eq!(detect_format(b"II*\0\x10\0\0\0CR\x02\0"), Some(PhotoFormat::Cr2));
+        assert_eq!(detect_format(&tiff_with_make(b"NIKON CORPORATION\0")), Some(PhotoFormat::Nef));
+        assert_eq!(detect_format(&tiff_with_make(b"SONY\0")), Some(PhotoFormat::Arw));
+        assert_eq!(detect_format(&tiff_with_make(b"Hasselblad\0")), Some(PhotoFormat::Tiff));
+        assert_eq!(detect_format(b"plain text"), None);
+    }
+
+    #[test]
+    fn test_embedded_preview_picks_largest_jpeg() {
+        let encode = |width, height| {
+            let mut bytes = Vec::new();
+            image::DynamicImage::new_rgb8(width, height)
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+                .unwrap();
+            bytes
+        };
+        let mut raw = tiff_with_make(b"NIKON CORPORATION\0");
+        raw.extend(encode(16, 8));
+        raw.extend(vec![0u8; 64]);
+        raw.extend(encode(64, 48));
+
+        let (offset, dimensions) = embedded_preview(&raw).unwrap();
+        assert_eq!((offset, dimensions), (raw.len() - encode(64, 48).len(), (64, 48)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.nef");
+        std::fs::write(&path, &raw).unwrap();
+        let (offset, _) = find_embedded_preview(&path).unwrap().unwrap();
+        assert_eq!(decode_jpeg_at(&path, offset).unwrap().dimensions(), (64, 48));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::error::{PhotoVaultError, Result};
+use crate::models::drive::DriveRole;
+use crate::models::scrub::FileStatus;
+use crate::services::file_ops::hash_file_sync;
+use crate::services::library_path;
+use log::warn;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+
+/// How many times a backup copy that fails verification is copied again.
+const COPY_RETRIES: usize = 2;
+
+/// SHA-256 of a file, computed off the async runtime.
+pub async fn hash_file(path: &Path) -> Result<String> {
+    let file = path.to_path_buf();
+    tokio::task::spawn_blocking(move || hash_file_sync(&file).map_err(|e| PhotoVaultError::io(&file, e))).await?
+}
+
+/// Records that a photo's file on `drive` was found to match its hash.
+pub async fn record_verification(pool: &SqlitePool, photo_id: i64, drive: DriveRole) -> Result<()> {
+    record_check(pool, photo_id, drive, FileStatus::Ok).await
+}
+
+/// Records the outcome of checking a photo's file on `drive`.
+pub async fn record_check(pool: &SqlitePool, photo_id: i64, drive: DriveRole, status: FileStatus) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO photo_verifications (photo_id, drive, verified_at, status) VALUES (?, ?, CURRENT_TIMESTAMP, ?)
+         ON CONFLICT(photo_id, drive) DO UPDATE SET verified_at = excluded.verified_at, status = excluded.status",
+    )
+    .bind(photo_id)
+    .bind(drive.as_str())
+    .bind(status.as_str())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Checks the file at catalog path `path` on the drive at `root` against
+/// `expected`.
+pub async fn check_file(root: &Path, path: &str, expected: &str) -> Result<FileStatus> {
+    match hash_file(&library_path::resolve(root, path)?).await {
+        Ok(hash) if hash == expected => Ok(FileStatus::Ok),
+        Ok(_) => Ok(FileStatus::Corrupt),
+        Err(PhotoVaultError::NotFound { .. }) => Ok(FileStatus::Missing),
+        Err(e) => Err(e),
+    }
+}
+
+/// Checks the backup copy of the photo at catalog path `path` against the
+/// hash the primary catalog holds for it. A copy that does not match is
+/// copied again from the primary, up to `COPY_RETRIES` times, going through
+/// `scratch` so the bad copy is only replaced by a good one. Photos the
+/// primary has not catalogued are not checked.
+pub async fn verify_backup_copy(
+    primary_db: &SqlitePool,
+    primary_root: &Path,
+    backup_db: &SqlitePool,
+    backup_root: &Path,
+    path: &str,
+    scratch: &Path,
+) -> Result<()> {
+    let photo: Option<(i64, String)> = sqlx::query_as("SELECT id, file_hash FROM photos WHERE path = ?")
+        .bind(path)
+        .fetch_optional(primary_db)
+        .await?;
+    let Some((photo_id, expected)) = photo else {
+        return Ok(());
+    };
+    let copy = library_path::resolve(backup_root, path)?;
+
+    let mut attempt = 0;
+    while hash_file(&copy).await? != expected {
+        if attempt == COPY_RETRIES {
+            return Err(PhotoVaultError::ChecksumMismatch {
+                drive: DriveRole::Backup,
+                path: path.to_string(),
+            });
+        }
+        attempt += 1;
+        warn!("Backup copy of {} does not match its hash, copying it again", path);
+        recopy(&library_path::resolve(primary_root, path)?, &copy, scratch).await?;
+    }
+
+    // The backup catalog hashed the copy when it was imported, which may
+    // have been a bad copy.
+    sqlx::query("UPDATE photos SET file_hash = ? WHERE path = ?")
+        .bind(&expected)
+        .bind(path)
+        .execute(backup_db)
+        .await?;
+    record_verification(primary_db, photo_id, DriveRole::Backup).await
+}
+
+/// Copies `original` over `copy` by way of a file in `scratch`, which must
+/// be on the same drive as `copy`.
+pub async fn recopy(original: &Path, copy: &Path, scratch: &Path) -> Result<()> {
+    for folder in [Some(scratch), copy.parent()].into_iter().flatten() {
+        tokio::fs::create_dir_all(folder)
+            .await
+            .map_err(|e| PhotoVaultError::io(folder, e))?;
+    }
+    let temp: PathBuf = scratch.join(copy.file_name().unwrap_or_default());
+    tokio::fs::copy(original, &temp)
+        .await
+        .map_err(|e| PhotoVaultError::io(original, e))?;
+    tokio::fs::rename(&temp, copy)
+        .await
+        .map_err(|e| PhotoVaultError::io(copy, e))
+}
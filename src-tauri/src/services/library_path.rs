@@ -0,0 +1,151 @@
+use crate::error::{PhotoVaultError, Result};
+use crate::models::operation::Operation;
+use log::{info, warn};
+use sqlx::SqlitePool;
+use std::path::{Component, Path, PathBuf};
+
+// Catalog paths are relative to the library root and always separated by
+// `/`, so a catalog stays valid wherever its drive is mounted.
+
+/// Converts a path on a drive to its catalog form. Returns `None` when the
+/// path does not lie under `root`.
+pub fn to_catalog_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Resolves a catalog path against the root the drive is mounted at. Paths
+/// that would escape the root are rejected.
+pub fn resolve(root: &Path, catalog_path: &str) -> Result<PathBuf> {
+    let mut path = root.to_path_buf();
+    for part in catalog_path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Err(PhotoVaultError::invalid_input(format!("{} leaves the library", catalog_path))),
+            part if Path::new(part).is_absolute() || part.contains('\\') => {
+                return Err(PhotoVaultError::invalid_input(format!("{} is not a library path", catalog_path)))
+            }
+            part => path.push(part),
+        }
+    }
+    Ok(path)
+}
+
+/// Joins a file name onto a catalog folder, `""` being the library root.
+pub fn join(folder: &str, name: &str) -> String {
+    let folder = folder.trim_matches('/');
+    if folder.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", folder, name)
+    }
+}
+
+/// Rewrites catalogs written before paths were stored relative to the
+/// library root. Absolute paths under `root` become relative, in `photos` and
+/// in the journal of operations the backup still owes. Rows that are already
+/// relative are left alone, so running it again does nothing.
+pub async fn migrate_absolute_paths(pool: &SqlitePool, root: &Path) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    let mut migrated = 0;
+
+    let photos: Vec<(i64, String)> = sqlx::query_as("SELECT id, path FROM photos").fetch_all(&mut *tx).await?;
+    for (id, path) in photos {
+        if !Path::new(&path).is_absolute() {
+            continue;
+        }
+        match to_catalog_path(root, Path::new(&path)) {
+            Some(relative) => {
+                sqlx::query("UPDATE photos SET path = ? WHERE id = ?")
+                    .bind(relative)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                migrated += 1;
+            }
+            None => warn!("Catalogued photo {} is outside the library at {}", path, root.display()),
+        }
+    }
+
+    let operations: Vec<(String, String)> =
+        sqlx::query_as("SELECT id, params FROM sync_operations WHERE status IN ('pending', 'failed')")
+            .fetch_all(&mut *tx)
+            .await?;
+    for (id, params) in operations {
+        let Ok(operation) = serde_json::from_str::<Operation>(&params) else {
+            continue;
+        };
+        if let Some(operation) = relativize_operation(operation, root) {
+            sqlx::query("UPDATE sync_operations SET params = ? WHERE id = ?")
+                .bind(serde_json::to_string(&operation).unwrap_or(params))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            migrated += 1;
+        }
+    }
+
+    tx.commit().await?;
+    if migrated > 0 {
+        info!("Converted {} catalog paths to be relative to {}", migrated, root.display());
+    }
+    Ok(migrated)
+}
+
+/// Returns the operation with its paths made relative, or `None` if it had
+/// no absolute paths under `root`.
+fn relativize_operation(operation: Operation, root: &Path) -> Option<Operation> {
+    let relativize = |path: &str| {
+        Path::new(path)
+            .is_absolute()
+            .then(|| to_catalog_path(root, Path::new(path)))
+            .flatten()
+    };
+    match operation {
+        Operation::Move { from, to } => {
+            let (new_from, new_to) = (relativize(&from), relativize(&to));
+            if new_from.is_none() && new_to.is_none() {
+                return None;
+            }
+            Some(Operation::Move {
+                from: new_from.unwrap_or(from),
+                to: new_to.unwrap_or(to),
+            })
+        }
+        Operation::Delete { path } => relativize(&path).map(|path| Operation::Delete { path }),
+        Operation::Rename { path, new_name } => {
+            relativize(&path).map(|path| Operation::Rename { path, new_name })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_paths_round_trip_and_stay_inside_root() {
+        let root = Path::new("/Volumes/Photos");
+        let file = root.join("2024").join("trip").join("a.jpg");
+        assert_eq!(to_catalog_path(root, &file).as_deref(), Some("2024/trip/a.jpg"));
+        assert_eq!(to_catalog_path(root, Path::new("/elsewhere/a.jpg")), None);
+
+        let remounted = Path::new("/media/me/Photos");
+        assert_eq!(
+            resolve(remounted, "2024/trip/a.jpg").unwrap(),
+            remounted.join("2024").join("trip").join("a.jpg")
+        );
+        assert!(resolve(remounted, "../secret.jpg").is_err());
+        assert_eq!(join("", "a.jpg"), "a.jpg");
+        assert_eq!(join("2024/", "a.jpg"), "2024/a.jpg");
+    }
+}
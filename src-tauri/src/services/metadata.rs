@@ -0,0 +1,174 @@
+use crate::models::exif::PhotoExif;
+use crate::services::format::{self, PhotoFormat};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use exif::{Context, Field, In, Reader, Tag, Value};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+const EXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+
+/// Upper bound on the bytes searched for the CR3 metadata boxes.
+const CR3_HEADER_LEN: u64 = 1024 * 1024;
+
+/// Reads EXIF metadata from JPEG, TIFF (including TIFF-based RAW), HEIF,
+/// PNG, WebP and CR3 files. Returns `None` when the file carries no EXIF.
+pub fn read_exif(path: &Path, format: PhotoFormat) -> Option<PhotoExif> {
+    let file = File::open(path).ok()?;
+    if format == PhotoFormat::Cr3 {
+        let mut header = Vec::new();
+        file.take(CR3_HEADER_LEN).read_to_end(&mut header).ok()?;
+        return read_cr3_exif(&header);
+    }
+
+    let exif = Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    Some(parse_exif(&|tag| exif.get_field(tag, In::PRIMARY)))
+}
+
+/// CR3 stores its metadata as separate TIFF structures in the `CMT1` (IFD0),
+/// `CMT2` (Exif IFD) and `CMT4` (GPS IFD) boxes. Each is parsed on its own,
+/// so every field is read back under the TIFF context.
+fn read_cr3_exif(header: &[u8]) -> Option<PhotoExif> {
+    let read_block = |box_type: &[u8; 4]| {
+        let body = format::find_boxes(header, box_type).next()?;
+        Reader::new().read_raw(body.to_vec()).ok()
+    };
+    let ifd0 = read_block(b"CMT1");
+    let exif_ifd = read_block(b"CMT2");
+    let gps_ifd = read_block(b"CMT4");
+    if ifd0.is_none() && exif_ifd.is_none() {
+        return None;
+    }
+
+    Some(parse_exif(&|tag: Tag| {
+        let block = match tag.context() {
+            Context::Tiff => ifd0.as_ref(),
+            Context::Exif => exif_ifd.as_ref(),
+            Context::Gps => gps_ifd.as_ref(),
+            _ => None,
+        }?;
+        block.get_field(Tag(Context::Tiff, tag.number()), In::PRIMARY)
+    }))
+}
+
+type FieldLookup<'a> = dyn Fn(Tag) -> Option<&'a Field> + 'a;
+
+fn parse_exif(exif: &FieldLookup) -> PhotoExif {
+    PhotoExif {
+        photo_id: 0,
+        date_time_original: ascii(exif, Tag::DateTimeOriginal)
+            .or_else(|| ascii(exif, Tag::DateTime)),
+        offset_time_original: ascii(exif, Tag::OffsetTimeOriginal)
+            .or_else(|| ascii(exif, Tag::OffsetTime)),
+        camera_make: ascii(exif, Tag::Make),
+        camera_model: ascii(exif, Tag::Model),
+        lens_model: ascii(exif, Tag::LensModel),
+        iso: uint(exif, Tag::PhotographicSensitivity),
+        aperture: rational(exif, Tag::FNumber),
+        exposure_time: rational(exif, Tag::ExposureTime),
+        focal_length: rational(exif, Tag::FocalLength),
+        orientation: uint(exif, Tag::Orientation),
+        gps_latitude: gps_coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S'),
+        gps_longitude: gps_coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W'),
+        gps_altitude: gps_altitude(exif),
+    }
+}
+
+/// Converts the camera's local capture time to UTC. Without a recorded offset
+/// the wall-clock time is taken as UTC, which keeps date filters stable.
+pub fn date_taken_utc(exif: &PhotoExif) -> Option<DateTime<Utc>> {
+    let local = exif.date_time_original.as_deref()?;
+    let naive = NaiveDateTime::parse_from_str(local, EXIF_DATE_FORMAT).ok()?;
+    let offset = exif
+        .offset_time_original
+        .as_deref()
+        .and_then(parse_offset)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    naive
+        .and_local_timezone(offset)
+        .single()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':')?;
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+    FixedOffset::east_opt(sign * seconds)
+}
+
+fn ascii(exif: &FieldLookup, tag: Tag) -> Option<String> {
+    match &exif(tag)?.value {
+        Value::Ascii(values) => {
+            let text = String::from_utf8_lossy(values.first()?);
+            let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            (!text.is_empty()).then(|| text.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn uint(exif: &FieldLookup, tag: Tag) -> Option<u32> {
+    exif(tag)?.value.get_uint(0)
+}
+
+fn rational(exif: &FieldLookup, tag: Tag) -> Option<f64> {
+    match &exif(tag)?.value {
+        Value::Rational(values) => values
+            .first()
+            .filter(|r| r.denom != 0)
+            .map(|r| r.to_f64()),
+        _ => None,
+    }
+}
+
+fn gps_coordinate(exif: &FieldLookup, tag: Tag, ref_tag: Tag, negative_ref: u8) -> Option<f64> {
+    let parts = match &exif(tag)?.value {
+        Value::Rational(values) if values.len() == 3 && values.iter().all(|r| r.denom != 0) => {
+            values.iter().map(|r| r.to_f64()).collect::<Vec<_>>()
+        }
+        _ => return None,
+    };
+    let degrees = parts[0] + parts[1] / 60.0 + parts[2] / 3600.0;
+    let is_negative = match &exif(ref_tag).map(|f| &f.value) {
+        Some(Value::Ascii(values)) => values.first().and_then(|v| v.first()) == Some(&negative_ref),
+        _ => false,
+    };
+    Some(if is_negative { -degrees } else { degrees })
+}
+
+fn gps_altitude(exif: &FieldLookup) -> Option<f64> {
+    let altitude = rational(exif, Tag::GPSAltitude)?;
+    // GPSAltitudeRef 1 means below sea level.
+    let below_sea_level = uint(exif, Tag::GPSAltitudeRef) == Some(1);
+    Some(if below_sea_level { -altitude } else { altitude })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_taken_utc_applies_offset() {
+        let exif = PhotoExif {
+            date_time_original: Some("2024:05:01 12:30:00".to_string()),
+            offset_time_original: Some("+02:00".to_string()),
+            ..Default::default()
+        };
+        let expected = DateTime::parse_from_rfc3339("2024-05-01T10:30:00Z").unwrap();
+        assert_eq!(date_taken_utc(&exif), Some(expected.with_timezone(&Utc)));
+
+        let without_offset = PhotoExif {
+            offset_time_original: None,
+            ..exif
+        };
+        let expected = DateTime::parse_from_rfc3339("2024-05-01T12:30:00Z").unwrap();
+        assert_eq!(date_taken_utc(&without_offset), Some(expected.with_timezone(&Utc)));
+    }
+}
@@ -1,10 +1,20 @@
 pub mod file_ops;
+pub mod integrity;
+pub mod scrub;
+pub mod format;
+pub mod metadata;
 pub mod sync_status;
+pub mod thumbnail;
 pub mod config;
+pub mod drive_identity;
+pub mod drive_monitor;
+pub mod drive_space;
+pub mod library_path;
 pub mod sync_engine;
 pub mod album;
 pub mod tags;
 pub mod filter;
 pub mod duplicate;
+pub mod similarity;
 pub mod rename;
 pub mod restore;
@@ -1,3 +1,4 @@
+use crate::error::Result;
 use crate::models::rename::{RenamePreview, RenameResult};
 
 pub struct RenameService;
@@ -6,7 +7,7 @@ impl RenameService {
     pub async fn bulk_rename(
         photo_ids: Vec<i64>,
         pattern: String,
-    ) -> Result<Vec<RenameResult>, String> {
+    ) -> Result<Vec<RenameResult>> {
         // Logic to bulk rename photos
         Ok(vec![])
     }
@@ -14,7 +15,7 @@ impl RenameService {
     pub async fn preview_bulk_rename(
         photo_ids: Vec<i64>,
         pattern: String,
-    ) -> Result<Vec<RenamePreview>, String> {
+    ) -> Result<Vec<RenamePreview>> {
         // Logic to preview bulk rename
         Ok(vec![])
     }
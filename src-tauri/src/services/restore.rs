@@ -1,18 +1,798 @@
-use crate::models::restore::RestoreSummary;
+use crate::db;
+use crate::error::{PhotoVaultError, Result};
+use crate::models::drive::DriveRole;
+use crate::models::exif::PhotoExif;
+use crate::models::photo::{Photo, PhotoMetadata};
+use crate::models::restore::{
+    DifferenceKind, FileDifference, RestoreAction, RestoreChange, RestoreFailure, RestoreOutcome, RestorePlan,
+    RestoreProgress, RestoreReport, RestoreSelection, RestoreSummary,
+};
+use crate::models::scrub::FileStatus;
+use crate::services::drive_space;
+use crate::services::file_ops::{is_hidden, FileOperationService};
+use crate::services::integrity;
+use crate::services::library_path;
+use chrono::Utc;
+use log::{info, warn};
+use sqlx::SqlitePool;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use walkdir::WalkDir;
 
-pub struct RestoreService;
+pub type RestoreProgressCallback = Arc<dyn Fn(&RestoreProgress) + Send + Sync>;
+
+/// Compares the primary and backup drives, both their files and their
+/// catalogs, and restores photos from the backup to the primary.
+pub struct RestoreService {
+    primary_db: SqlitePool,
+    primary_root: PathBuf,
+    backup_db: SqlitePool,
+    backup_root: PathBuf,
+}
 
 impl RestoreService {
-    pub async fn detect_differences() -> Result<RestoreSummary, String> {
-        // Logic to detect differences between primary and backup
-        Ok(RestoreSummary {
-            missing_files: 0,
-            different_files: 0,
+    pub fn new(primary_db: SqlitePool, primary_root: PathBuf, backup_db: SqlitePool, backup_root: PathBuf) -> Self {
+        Self {
+            primary_db,
+            primary_root,
+            backup_db,
+            backup_root,
+        }
+    }
+
+    /// Lists every file that differs between the drives. Files are compared
+    /// by size on disk and by the hashes in the catalogs, so nothing is
+    /// re-read; the integrity scrub is what catches a file whose contents
+    /// changed under its catalog entry.
+    pub async fn all_differences(&self) -> Result<Vec<FileDifference>> {
+        let (primary_files, backup_files) =
+            tokio::try_join!(list_files(&self.primary_root), list_files(&self.backup_root))?;
+        let primary_catalog = load_catalog(&self.primary_db).await?;
+        let backup_catalog = load_catalog(&self.backup_db).await?;
+
+        let paths: BTreeSet<&String> = primary_files.keys().chain(backup_files.keys()).collect();
+        let mut differences = Vec::new();
+        for path in paths {
+            let primary_size = primary_files.get(path).copied();
+            let backup_size = backup_files.get(path).copied();
+            let (primary_photo, backup_photo) = (primary_catalog.get(path), backup_catalog.get(path));
+            let tracked = primary_photo.is_some() || backup_photo.is_some();
+
+            let (kind, drive) = match (primary_size, backup_size) {
+                (Some(_), None) if tracked => (DifferenceKind::MissingOnBackup, None),
+                (None, Some(_)) if tracked => (DifferenceKind::MissingOnPrimary, None),
+                (Some(_), None) => (DifferenceKind::ExtraUntracked, Some(DriveRole::Primary)),
+                (None, Some(_)) => (DifferenceKind::ExtraUntracked, Some(DriveRole::Backup)),
+                (Some(primary_size), Some(backup_size)) => {
+                    let hashes_differ = matches!(
+                        (primary_photo, backup_photo),
+                        (Some(a), Some(b)) if a.file_hash != b.file_hash
+                    );
+                    if primary_size != backup_size || hashes_differ {
+                        (DifferenceKind::ContentDiffers, None)
+                    } else if tracked && !same_metadata(primary_photo, backup_photo) {
+                        (DifferenceKind::MetadataDiffers, None)
+                    } else {
+                        continue;
+                    }
+                }
+                (None, None) => continue,
+            };
+            differences.push(FileDifference {
+                path: path.clone(),
+                kind,
+                drive,
+                primary_size,
+                backup_size,
+            });
+        }
+        Ok(differences)
+    }
+
+    /// The summary of all differences and one page of them, optionally only
+    /// those of `kind`.
+    pub async fn detect_differences(
+        &self,
+        kind: Option<DifferenceKind>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<RestoreReport> {
+        let mut summary = RestoreSummary::default();
+        let mut matching = Vec::new();
+        for difference in self.all_differences().await? {
+            summary.count(difference.kind);
+            if kind.is_none() || kind == Some(difference.kind) {
+                matching.push(difference);
+            }
+        }
+        Ok(RestoreReport {
+            summary,
+            total: matching.len() as u64,
+            differences: matching.into_iter().skip(offset).take(limit).collect(),
         })
     }
 
-    pub async fn restore_backup_to_primary() -> Result<(), String> {
-        // Logic to restore backup to primary
+    /// Works out what restoring `selection` would change on the primary,
+    /// without changing anything.
+    pub async fn plan(&self, selection: &RestoreSelection) -> Result<RestorePlan> {
+        let mut plan = RestorePlan::default();
+        for photo in self.selected_photos(selection).await? {
+            match self.plan_photo(&photo).await {
+                Ok(Some(change)) => plan.changes.push(change),
+                Ok(None) => {}
+                Err(error) => {
+                    warn!("Cannot restore {}: {}", photo.path, error);
+                    plan.skipped.push(RestoreFailure { path: photo.path, error });
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Brings the selected photos back from the backup: files are copied
+    /// after checking the backup's copy against its hash and checked again
+    /// once on the primary, then their catalog entries, albums and tags are
+    /// copied over. A photo that fails, or that the plan had to skip, does not
+    /// stop the others.
+    pub async fn restore(
+        &self,
+        selection: &RestoreSelection,
+        cancel: Arc<AtomicBool>,
+        on_progress: RestoreProgressCallback,
+    ) -> Result<RestoreOutcome> {
+        let plan = self.plan(selection).await?;
+        let mut outcome = self.apply_all(plan.changes, DriveRole::Primary, cancel, on_progress).await?;
+        outcome.failed.extend(plan.skipped);
+        info!(
+            "Restored {} photos from the backup, {} failed",
+            outcome.restored.len(),
+            outcome.failed.len()
+        );
+        Ok(outcome)
+    }
+
+    /// Works out what mirroring the primary onto the backup would change,
+    /// from the same comparison `detect_differences` reports.
+    pub async fn plan_mirror(&self) -> Result<Vec<RestoreChange>> {
+        Ok(self
+            .all_differences()
+            .await?
+            .into_iter()
+            .map(|difference| {
+                let action = match (difference.kind, difference.drive) {
+                    (DifferenceKind::MissingOnBackup, _) => RestoreAction::CopyFile,
+                    (DifferenceKind::ExtraUntracked, Some(DriveRole::Primary)) => RestoreAction::CopyFile,
+                    (DifferenceKind::ContentDiffers, _) => RestoreAction::ReplaceFile,
+                    (DifferenceKind::MetadataDiffers, _) => RestoreAction::UpdateCatalog,
+                    (DifferenceKind::MissingOnPrimary | DifferenceKind::ExtraUntracked, _) => RestoreAction::Quarantine,
+                };
+                let copied = matches!(action, RestoreAction::CopyFile | RestoreAction::ReplaceFile);
+                RestoreChange {
+                    path: difference.path,
+                    action,
+                    size: if copied { difference.primary_size.unwrap_or(0) } else { 0 },
+                }
+            })
+            .collect())
+    }
+
+    /// Makes the backup a copy of the primary: files it lacks or holds a
+    /// different copy of are copied over and checked, files the primary does
+    /// not have are moved into `.photovault/quarantine/<time>` on the backup,
+    /// and the backup catalog is rewritten from the primary's. Files that
+    /// failed are left out of the rewritten catalog, so it never lists a copy
+    /// the backup does not hold.
+    pub async fn mirror(&self, cancel: Arc<AtomicBool>, on_progress: RestoreProgressCallback) -> Result<RestoreOutcome> {
+        let changes = self.plan_mirror().await?;
+        let mut outcome = self.apply_all(changes, DriveRole::Backup, cancel, on_progress).await?;
+        if outcome.cancelled {
+            outcome.catalog_stale = true;
+        } else {
+            let failed: Vec<String> = outcome.failed.iter().map(|failure| failure.path.clone()).collect();
+            db::copy_library_tables(&self.primary_db, &self.backup_db, &failed).await?;
+            outcome.catalog_stale = !failed.is_empty();
+        }
+        info!(
+            "Mirrored {} files onto the backup, {} failed",
+            outcome.restored.len(),
+            outcome.failed.len()
+        );
+        Ok(outcome)
+    }
+
+    /// Applies `changes` to the drive in role `target` from the other drive.
+    async fn apply_all(
+        &self,
+        changes: Vec<RestoreChange>,
+        target: DriveRole,
+        cancel: Arc<AtomicBool>,
+        on_progress: RestoreProgressCallback,
+    ) -> Result<RestoreOutcome> {
+        let bytes_total = changes.iter().map(|change| change.size).sum();
+        drive_space::ensure_space(self.root(target), target, bytes_total)?;
+
+        let quarantine = self
+            .root(target)
+            .join(".photovault")
+            .join("quarantine")
+            .join(Utc::now().format("%Y%m%d-%H%M%S").to_string());
+        let mut progress = RestoreProgress {
+            files_total: changes.len() as u64,
+            files_done: 0,
+            bytes_total,
+            bytes_done: 0,
+            current_path: None,
+        };
+        let mut outcome = RestoreOutcome::default();
+        for change in changes {
+            if cancel.load(Ordering::Relaxed) {
+                outcome.cancelled = true;
+                break;
+            }
+            progress.current_path = Some(change.path.clone());
+            on_progress(&progress);
+
+            let applied = match target {
+                DriveRole::Primary => self.apply(&change).await,
+                DriveRole::Backup => self.apply_to_backup(&change, &quarantine).await,
+            };
+            match applied {
+                Ok(()) => outcome.restored.push(change.clone()),
+                Err(error) => {
+                    warn!("Failed to restore {}: {}", change.path, error);
+                    outcome.failed.push(RestoreFailure {
+                        path: change.path.clone(),
+                        error,
+                    });
+                }
+            }
+            progress.files_done += 1;
+            progress.bytes_done += change.size;
+        }
+        let _ = tokio::fs::remove_dir(self.scratch(target)).await;
+        progress.current_path = None;
+        on_progress(&progress);
+        Ok(outcome)
+    }
+
+    /// Applies one change of a mirror. The catalog is rewritten as a whole
+    /// afterwards, so only files are touched here.
+    async fn apply_to_backup(&self, change: &RestoreChange, quarantine: &Path) -> Result<()> {
+        let source = library_path::resolve(&self.primary_root, &change.path)?;
+        let target = library_path::resolve(&self.backup_root, &change.path)?;
+        match change.action {
+            RestoreAction::CopyFile | RestoreAction::ReplaceFile => {
+                let primary: Option<(i64, String)> = sqlx::query_as("SELECT id, file_hash FROM photos WHERE path = ?")
+                    .bind(&change.path)
+                    .fetch_optional(&self.primary_db)
+                    .await?;
+                // A bad copy on the primary is not spread to the backup.
+                if let Some((_, file_hash)) = &primary {
+                    if integrity::check_file(&self.primary_root, &change.path, file_hash).await? != FileStatus::Ok {
+                        return Err(PhotoVaultError::ChecksumMismatch {
+                            drive: DriveRole::Primary,
+                            path: change.path.clone(),
+                        });
+                    }
+                }
+                integrity::recopy(&source, &target, &self.scratch(DriveRole::Backup)).await?;
+                if let Some((photo_id, file_hash)) = primary {
+                    if integrity::check_file(&self.backup_root, &change.path, &file_hash).await? != FileStatus::Ok {
+                        return Err(PhotoVaultError::ChecksumMismatch {
+                            drive: DriveRole::Backup,
+                            path: change.path.clone(),
+                        });
+                    }
+                    integrity::record_verification(&self.primary_db, photo_id, DriveRole::Backup).await?;
+                }
+            }
+            RestoreAction::Quarantine => {
+                let kept = library_path::resolve(quarantine, &change.path)?;
+                if let Some(parent) = kept.parent() {
+                    tokio::fs::create_dir_all(parent)
+                        .await
+                        .map_err(|e| PhotoVaultError::io(parent, e))?;
+                }
+                tokio::fs::rename(&target, &kept)
+                    .await
+                    .map_err(|e| PhotoVaultError::io(&target, e))?;
+            }
+            RestoreAction::UpdateCatalog => {}
+        }
         Ok(())
     }
+
+    /// The backup's catalog entries of the photos in `selection`.
+    async fn selected_photos(&self, selection: &RestoreSelection) -> Result<Vec<Photo>> {
+        let backup_photos = sqlx::query_as::<_, Photo>("SELECT * FROM photos ORDER BY path")
+            .fetch_all(&self.backup_db)
+            .await?;
+        let selected: Box<dyn Fn(&Photo) -> bool + Send> = match selection {
+            RestoreSelection::AllDifferences => {
+                let paths: HashSet<String> = self
+                    .all_differences()
+                    .await?
+                    .into_iter()
+                    .filter(|difference| {
+                        matches!(
+                            difference.kind,
+                            DifferenceKind::MissingOnPrimary
+                                | DifferenceKind::ContentDiffers
+                                | DifferenceKind::MetadataDiffers
+                        )
+                    })
+                    .map(|difference| difference.path)
+                    .collect();
+                Box::new(move |photo| paths.contains(&photo.path))
+            }
+            RestoreSelection::Photos { photo_ids } => {
+                let mut paths = HashSet::new();
+                for photo_id in photo_ids {
+                    let path: Option<String> = sqlx::query_scalar("SELECT path FROM photos WHERE id = ?")
+                        .bind(photo_id)
+                        .fetch_optional(&self.primary_db)
+                        .await?;
+                    paths.insert(path.ok_or_else(|| PhotoVaultError::not_found(format!("Photo {}", photo_id)))?);
+                }
+                Box::new(move |photo| paths.contains(&photo.path))
+            }
+            RestoreSelection::Folder { folder } => {
+                let folder = folder.trim_matches('/').to_string();
+                let prefix = format!("{}/", folder);
+                Box::new(move |photo| folder.is_empty() || photo.path.starts_with(&prefix))
+            }
+            RestoreSelection::DateRange { from, to } => {
+                let (from, to) = (*from, *to);
+                Box::new(move |photo| {
+                    photo.date_taken.is_some_and(|taken| {
+                        (from.is_none() || from <= Some(taken)) && (to.is_none() || Some(taken) < to)
+                    })
+                })
+            }
+        };
+        Ok(backup_photos.into_iter().filter(|photo| selected(photo)).collect())
+    }
+
+    /// What restoring one of the backup's photos would change, if anything.
+    async fn plan_photo(&self, backup: &Photo) -> Result<Option<RestoreChange>> {
+        let source = library_path::resolve(&self.backup_root, &backup.path)?;
+        let size = tokio::fs::metadata(&source)
+            .await
+            .map_err(|e| PhotoVaultError::io(&source, e))?
+            .len();
+        let target = library_path::resolve(&self.primary_root, &backup.path)?;
+        let primary = sqlx::query_as::<_, Photo>("SELECT * FROM photos WHERE path = ?")
+            .bind(&backup.path)
+            .fetch_optional(&self.primary_db)
+            .await?;
+
+        let action = match tokio::fs::metadata(&target).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => RestoreAction::CopyFile,
+            Err(e) => return Err(PhotoVaultError::io(&target, e)),
+            Ok(metadata) => {
+                let differs = match &primary {
+                    Some(primary) => {
+                        metadata.len() != size
+                            || primary.file_hash != backup.file_hash
+                            || self.failed_check(primary.id).await?
+                    }
+                    None => integrity::hash_file(&target).await? != backup.file_hash,
+                };
+                if differs {
+                    RestoreAction::ReplaceFile
+                } else if !same_metadata(primary.as_ref(), Some(backup))
+                    || self.labels(&self.primary_db, primary.as_ref()).await?
+                        != self.labels(&self.backup_db, Some(backup)).await?
+                {
+                    RestoreAction::UpdateCatalog
+                } else {
+                    return Ok(None);
+                }
+            }
+        };
+        Ok(Some(RestoreChange {
+            path: backup.path.clone(),
+            action,
+            size: if action == RestoreAction::UpdateCatalog { 0 } else { size },
+        }))
+    }
+
+    /// Whether the integrity scrub found the primary's copy of a photo
+    /// corrupt or missing. Files edited since they were catalogued are left
+    /// alone.
+    async fn failed_check(&self, photo_id: i64) -> Result<bool> {
+        let status: Option<FileStatus> =
+            sqlx::query_scalar("SELECT status FROM photo_verifications WHERE photo_id = ? AND drive = ?")
+                .bind(photo_id)
+                .bind(DriveRole::Primary)
+                .fetch_optional(&self.primary_db)
+                .await?;
+        Ok(matches!(status, Some(FileStatus::Corrupt | FileStatus::Missing)))
+    }
+
+    /// Names of the albums and tags a photo is in, sorted.
+    async fn labels(&self, pool: &SqlitePool, photo: Option<&Photo>) -> Result<(Vec<String>, Vec<String>)> {
+        let Some(photo) = photo else {
+            return Ok(Default::default());
+        };
+        let albums = sqlx::query_scalar(
+            "SELECT a.name FROM albums a JOIN photo_album pa ON pa.album_id = a.id WHERE pa.photo_id = ? ORDER BY a.name",
+        )
+        .bind(photo.id)
+        .fetch_all(pool)
+        .await?;
+        let tags = sqlx::query_scalar(
+            "SELECT t.name FROM tags t JOIN photo_tag pt ON pt.tag_id = t.id WHERE pt.photo_id = ? ORDER BY t.name",
+        )
+        .bind(photo.id)
+        .fetch_all(pool)
+        .await?;
+        Ok((albums, tags))
+    }
+
+    async fn apply(&self, change: &RestoreChange) -> Result<()> {
+        let backup = sqlx::query_as::<_, Photo>("SELECT * FROM photos WHERE path = ?")
+            .bind(&change.path)
+            .fetch_optional(&self.backup_db)
+            .await?
+            .ok_or_else(|| PhotoVaultError::not_found(&change.path))?;
+
+        let copied = change.action != RestoreAction::UpdateCatalog;
+        if copied {
+            if integrity::check_file(&self.backup_root, &backup.path, &backup.file_hash).await? != FileStatus::Ok {
+                return Err(PhotoVaultError::ChecksumMismatch {
+                    drive: DriveRole::Backup,
+                    path: backup.path,
+                });
+            }
+            integrity::recopy(
+                &library_path::resolve(&self.backup_root, &backup.path)?,
+                &library_path::resolve(&self.primary_root, &backup.path)?,
+                &self.scratch(DriveRole::Primary),
+            )
+            .await?;
+            if integrity::check_file(&self.primary_root, &backup.path, &backup.file_hash).await? != FileStatus::Ok {
+                return Err(PhotoVaultError::ChecksumMismatch {
+                    drive: DriveRole::Primary,
+                    path: backup.path,
+                });
+            }
+        }
+
+        let exif = sqlx::query_as::<_, PhotoExif>("SELECT * FROM photo_exif WHERE photo_id = ?")
+            .bind(backup.id)
+            .fetch_optional(&self.backup_db)
+            .await?;
+        let (albums, tags) = self.labels(&self.backup_db, Some(&backup)).await?;
+        let mut photo = backup.clone();
+        photo.id = 0;
+        let restored = FileOperationService::new(self.primary_root.clone(), self.primary_db.clone())
+            .save_photo(PhotoMetadata { photo, exif })
+            .await?;
+        copy_labels(&self.primary_db, restored.id, &albums, &tags).await?;
+        if copied {
+            integrity::record_verification(&self.primary_db, restored.id, DriveRole::Primary).await?;
+            integrity::record_verification(&self.primary_db, restored.id, DriveRole::Backup).await?;
+        }
+        Ok(())
+    }
+
+    fn root(&self, drive: DriveRole) -> &Path {
+        match drive {
+            DriveRole::Primary => &self.primary_root,
+            DriveRole::Backup => &self.backup_root,
+        }
+    }
+
+    /// Where copies are written before they replace a file on `drive`.
+    fn scratch(&self, drive: DriveRole) -> PathBuf {
+        self.root(drive).join(".photovault").join("restore")
+    }
+}
+
+/// Puts a photo in exactly the named albums and tags, creating any the
+/// catalog does not have yet.
+async fn copy_labels(pool: &SqlitePool, photo_id: i64, albums: &[String], tags: &[String]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    for (table, link, column, names) in [
+        ("albums", "photo_album", "album_id", albums),
+        ("tags", "photo_tag", "tag_id", tags),
+    ] {
+        sqlx::query(&format!("DELETE FROM {} WHERE photo_id = ?", link))
+            .bind(photo_id)
+            .execute(&mut *tx)
+            .await?;
+        for name in names {
+            sqlx::query(&format!("INSERT OR IGNORE INTO {} (name) VALUES (?)", table))
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(&format!(
+                "INSERT OR IGNORE INTO {} (photo_id, {}) SELECT ?, id FROM {} WHERE name = ?",
+                link, column, table
+            ))
+            .bind(photo_id)
+            .bind(name)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sizes of the library's files on a drive, by catalog path.
+async fn list_files(root: &Path) -> Result<HashMap<String, u64>> {
+    let root = root.to_path_buf();
+    let files = tokio::task::spawn_blocking(move || {
+        let mut files = HashMap::new();
+        for entry in WalkDir::new(&root).into_iter().filter_entry(|e| !is_hidden(e)) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Failed to read directory entry: {}", e);
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = library_path::to_catalog_path(&root, entry.path());
+            let (Some(path), Ok(metadata)) = (path, entry.metadata()) else {
+                continue;
+            };
+            files.insert(path, metadata.len());
+        }
+        files
+    })
+    .await?;
+    Ok(files)
+}
+
+async fn load_catalog(pool: &SqlitePool) -> Result<HashMap<String, Photo>> {
+    let photos = sqlx::query_as::<_, Photo>("SELECT * FROM photos").fetch_all(pool).await?;
+    Ok(photos.into_iter().map(|photo| (photo.path.clone(), photo)).collect())
+}
+
+/// Whether both catalogs hold the same data for a file. A file only one of
+/// them tracks does not match.
+fn same_metadata(primary: Option<&Photo>, backup: Option<&Photo>) -> bool {
+    let (Some(a), Some(b)) = (primary, backup) else {
+        return false;
+    };
+    a.filename == b.filename
+        && a.file_size == b.file_size
+        && a.date_taken == b.date_taken
+        && (a.width, a.height) == (b.width, b.height)
+        && a.format == b.format
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn test_pool(dir: &Path) -> SqlitePool {
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        crate::db::init_db(&dir.join("test.db"), &migrations).await.unwrap()
+    }
+
+    async fn catalog(pool: &SqlitePool, path: &str, hash: &str, width: u32) {
+        sqlx::query("INSERT INTO photos (path, filename, file_hash, width) VALUES (?, ?, ?, ?)")
+            .bind(path)
+            .bind(path.rsplit('/').next().unwrap())
+            .bind(hash)
+            .bind(width)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_detect_differences_classifies_each_file() {
+        let (primary, backup) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_dir, backup_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_db, backup_db) = (test_pool(primary_dir.path()).await, test_pool(backup_dir.path()).await);
+        let files: [(&str, Option<&str>, Option<&str>); 6] = [
+            ("same.jpg", Some("same"), Some("same")),
+            ("only-primary.jpg", Some("a"), None),
+            ("only-backup.jpg", None, Some("b")),
+            ("changed.jpg", Some("old"), Some("new!")),
+            ("retagged.jpg", Some("tags"), Some("tags")),
+            ("notes.txt", Some("untracked"), None),
+        ];
+        for (path, primary_contents, backup_contents) in files {
+            if let Some(contents) = primary_contents {
+                std::fs::write(primary.path().join(path), contents).unwrap();
+            }
+            if let Some(contents) = backup_contents {
+                std::fs::write(backup.path().join(path), contents).unwrap();
+            }
+            if path.ends_with(".jpg") {
+                let width = if path == "retagged.jpg" { 1 } else { 2 };
+                catalog(&primary_db, path, primary_contents.unwrap_or("-"), 2).await;
+                catalog(&backup_db, path, backup_contents.unwrap_or("-"), width).await;
+            }
+        }
+        std::fs::create_dir(primary.path().join(".photovault")).unwrap();
+        std::fs::write(primary.path().join(".photovault").join("drive.json"), "{}").unwrap();
+
+        let service = RestoreService::new(
+            primary_db,
+            primary.path().to_path_buf(),
+            backup_db,
+            backup.path().to_path_buf(),
+        );
+        let report = service.detect_differences(None, 10, 0).await.unwrap();
+        let kinds: Vec<(&str, DifferenceKind)> =
+            report.differences.iter().map(|d| (d.path.as_str(), d.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("changed.jpg", DifferenceKind::ContentDiffers),
+                ("notes.txt", DifferenceKind::ExtraUntracked),
+                ("only-backup.jpg", DifferenceKind::MissingOnPrimary),
+                ("only-primary.jpg", DifferenceKind::MissingOnBackup),
+                ("retagged.jpg", DifferenceKind::MetadataDiffers),
+            ]
+        );
+        assert_eq!(report.differences[1].drive, Some(DriveRole::Primary));
+        assert_eq!(report.total, 5);
+
+        let page = service.detect_differences(None, 2, 2).await.unwrap();
+        assert_eq!(page.differences[0].path, "only-backup.jpg");
+        assert_eq!(page.differences.len(), 2);
+        let missing = service
+            .detect_differences(Some(DifferenceKind::MissingOnBackup), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!((missing.total, missing.summary.content_differs), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_restore_copies_selected_files_and_catalog_rows_back() {
+        let (primary, backup) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_dir, backup_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_db, backup_db) = (test_pool(primary_dir.path()).await, test_pool(backup_dir.path()).await);
+        std::fs::create_dir(backup.path().join("2024")).unwrap();
+        for name in ["lost.jpg", "rotten.jpg"] {
+            let path = format!("2024/{}", name);
+            std::fs::write(backup.path().join(&path), name).unwrap();
+            let hash = integrity::hash_file(&backup.path().join(&path)).await.unwrap();
+            catalog(&backup_db, &path, &hash, 2).await;
+            if name == "rotten.jpg" {
+                std::fs::create_dir(primary.path().join("2024")).unwrap();
+                std::fs::write(primary.path().join(&path), "bit rot").unwrap();
+                catalog(&primary_db, &path, &hash, 2).await;
+            }
+        }
+        sqlx::query("INSERT INTO albums (name) VALUES ('Holidays')").execute(&backup_db).await.unwrap();
+        sqlx::query("INSERT INTO photo_album (photo_id, album_id) VALUES (1, 1)").execute(&backup_db).await.unwrap();
+        // Catalogued on the backup, but its file is gone from both drives.
+        catalog(&backup_db, "2024/gone.jpg", "gone", 2).await;
+
+        let service = RestoreService::new(
+            primary_db.clone(),
+            primary.path().to_path_buf(),
+            backup_db,
+            backup.path().to_path_buf(),
+        );
+        let dated = RestoreSelection::DateRange { from: None, to: Some(Utc::now()) };
+        assert!(service.plan(&dated).await.unwrap().changes.is_empty());
+        let selection = RestoreSelection::Folder { folder: "2024".into() };
+        let plan = service.plan(&selection).await.unwrap();
+        let actions: Vec<RestoreAction> = plan.changes.iter().map(|change| change.action).collect();
+        assert_eq!(actions, vec![RestoreAction::CopyFile, RestoreAction::ReplaceFile]);
+        // The dry run lists what cannot be restored, as the restore reports it.
+        let skipped: Vec<&str> = plan.skipped.iter().map(|failure| failure.path.as_str()).collect();
+        assert_eq!(skipped, vec!["2024/gone.jpg"]);
+        // A dry run changes nothing.
+        assert!(!primary.path().join("2024").join("lost.jpg").exists());
+
+        let outcome = service
+            .restore(&selection, Arc::new(AtomicBool::new(false)), Arc::new(|_: &RestoreProgress| {}))
+            .await
+            .unwrap();
+        assert_eq!((outcome.restored.len(), outcome.failed.len()), (2, 1));
+        assert_eq!(outcome.failed[0].path, "2024/gone.jpg");
+        for name in ["lost.jpg", "rotten.jpg"] {
+            assert_eq!(std::fs::read(primary.path().join("2024").join(name)).unwrap(), name.as_bytes());
+        }
+        let album: String = sqlx::query_scalar(
+            "SELECT a.name FROM albums a JOIN photo_album pa ON pa.album_id = a.id
+             JOIN photos p ON p.id = pa.photo_id WHERE p.path = '2024/lost.jpg'",
+        )
+        .fetch_one(&primary_db)
+        .await
+        .unwrap();
+        assert_eq!(album, "Holidays");
+        assert!(service.plan(&selection).await.unwrap().changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_makes_backup_match_primary_and_quarantines_extras() {
+        let (primary, backup) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_dir, backup_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_db, backup_db) = (test_pool(primary_dir.path()).await, test_pool(backup_dir.path()).await);
+        for name in ["kept.jpg", "deleted-from-backup.jpg", "changed.jpg"] {
+            std::fs::write(primary.path().join(name), name).unwrap();
+            let hash = integrity::hash_file(&primary.path().join(name)).await.unwrap();
+            catalog(&primary_db, name, &hash, 2).await;
+        }
+        std::fs::write(backup.path().join("kept.jpg"), "kept.jpg").unwrap();
+        std::fs::write(backup.path().join("changed.jpg"), "stale").unwrap();
+        std::fs::write(backup.path().join("stray.jpg"), "stray").unwrap();
+        catalog(&backup_db, "stray.jpg", "stray", 2).await;
+
+        let service = RestoreService::new(
+            primary_db.clone(),
+            primary.path().to_path_buf(),
+            backup_db.clone(),
+            backup.path().to_path_buf(),
+        );
+        let plan = service.plan_mirror().await.unwrap();
+        let actions: Vec<(&str, RestoreAction)> = plan.iter().map(|c| (c.path.as_str(), c.action)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("changed.jpg", RestoreAction::ReplaceFile),
+                ("deleted-from-backup.jpg", RestoreAction::CopyFile),
+                ("kept.jpg", RestoreAction::UpdateCatalog),
+                ("stray.jpg", RestoreAction::Quarantine),
+            ]
+        );
+
+        let outcome = service
+            .mirror(Arc::new(AtomicBool::new(false)), Arc::new(|_: &RestoreProgress| {}))
+            .await
+            .unwrap();
+        assert!(outcome.failed.is_empty());
+        for name in ["kept.jpg", "deleted-from-backup.jpg", "changed.jpg"] {
+            assert_eq!(std::fs::read(backup.path().join(name)).unwrap(), name.as_bytes());
+        }
+        assert!(!backup.path().join("stray.jpg").exists());
+        let quarantine = backup.path().join(".photovault").join("quarantine");
+        let batch = std::fs::read_dir(&quarantine).unwrap().next().unwrap().unwrap().path();
+        assert!(batch.join("stray.jpg").exists());
+        let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM photos ORDER BY id")
+            .fetch_all(&backup_db)
+            .await
+            .unwrap();
+        assert_eq!(paths, vec!["kept.jpg", "deleted-from-backup.jpg", "changed.jpg"]);
+        assert!(service.plan_mirror().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_leaves_failed_copies_out_of_the_backup_catalog() {
+        let (primary, backup) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_dir, backup_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let (primary_db, backup_db) = (test_pool(primary_dir.path()).await, test_pool(backup_dir.path()).await);
+        std::fs::write(primary.path().join("good.jpg"), "good").unwrap();
+        let hash = integrity::hash_file(&primary.path().join("good.jpg")).await.unwrap();
+        catalog(&primary_db, "good.jpg", &hash, 2).await;
+        // Does not match its catalogued hash, so it is never copied.
+        std::fs::write(primary.path().join("rotten.jpg"), "rotten").unwrap();
+        catalog(&primary_db, "rotten.jpg", "bogus", 2).await;
+
+        let service = RestoreService::new(
+            primary_db.clone(),
+            primary.path().to_path_buf(),
+            backup_db.clone(),
+            backup.path().to_path_buf(),
+        );
+        let outcome = service
+            .mirror(Arc::new(AtomicBool::new(false)), Arc::new(|_: &RestoreProgress| {}))
+            .await
+            .unwrap();
+
+        let failed: Vec<&str> = outcome.failed.iter().map(|failure| failure.path.as_str()).collect();
+        assert_eq!(failed, vec!["rotten.jpg"]);
+        assert!(outcome.catalog_stale);
+        assert!(!backup.path().join("rotten.jpg").exists());
+        let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM photos").fetch_all(&backup_db).await.unwrap();
+        assert_eq!(paths, vec!["good.jpg"]);
+    }
 }
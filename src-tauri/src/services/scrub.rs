@@ -0,0 +1,393 @@
+use crate::error::{PhotoVaultError, Result};
+use crate::models::drive::DriveRole;
+use crate::models::scrub::{FileStatus, ScrubIssue, ScrubProgress, ScrubSummary};
+use crate::services::file_ops;
+use crate::services::integrity;
+use crate::services::library_path;
+use log::{info, warn};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Days between the end of one scrub pass and the start of the next.
+pub const SCRUB_INTERVAL_DAYS: u32 = 30;
+
+/// How often the app checks whether a scrub is due.
+pub const SCRUB_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Number of photos read from the catalog at a time.
+const SCRUB_BATCH_SIZE: i64 = 100;
+
+/// Hashing is slowed down to this rate so a scrub does not get in the way of
+/// browsing the library.
+const SCRUB_BYTES_PER_SECOND: f64 = 32.0 * 1024.0 * 1024.0;
+
+pub type ScrubProgressCallback = Arc<dyn Fn(&ScrubProgress) + Send + Sync>;
+
+/// Re-hashes the library's files on both drives and compares them with the
+/// hashes in the primary catalog, where the outcome is recorded per file and
+/// per drive. A pass works through the photos by id and checkpoints as it
+/// goes, so a stopped pass picks up where it left off.
+pub struct ScrubService {
+    primary_db: SqlitePool,
+    primary_root: PathBuf,
+    /// Left out while the backup is offline or still owes queued
+    /// operations, since its files are then not where the catalog says.
+    backup_root: Option<PathBuf>,
+}
+
+impl ScrubService {
+    pub fn new(primary_db: SqlitePool, primary_root: PathBuf, backup_root: Option<PathBuf>) -> Self {
+        Self {
+            primary_db,
+            primary_root,
+            backup_root,
+        }
+    }
+
+    /// Whether a pass was interrupted or the last one finished more than
+    /// `SCRUB_INTERVAL_DAYS` ago.
+    pub async fn is_due(pool: &SqlitePool) -> Result<bool> {
+        let due: bool = sqlx::query_scalar(
+            "SELECT last_photo_id > 0 OR finished_at IS NULL OR finished_at < datetime('now', ?) FROM scrub_state",
+        )
+        .bind(format!("-{} days", SCRUB_INTERVAL_DAYS))
+        .fetch_one(pool)
+        .await?;
+        Ok(due)
+    }
+
+    pub async fn run(&self, cancel: Arc<AtomicBool>, on_progress: ScrubProgressCallback) -> Result<ScrubSummary> {
+        let last_photo_id: i64 = sqlx::query_scalar("SELECT last_photo_id FROM scrub_state")
+            .fetch_one(&self.primary_db)
+            .await?;
+        if last_photo_id == 0 {
+            sqlx::query("UPDATE scrub_state SET started_at = CURRENT_TIMESTAMP")
+                .execute(&self.primary_db)
+                .await?;
+        } else {
+            info!("Resuming integrity scrub after photo {}", last_photo_id);
+        }
+        let photos_total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos")
+            .fetch_one(&self.primary_db)
+            .await?;
+        let photos_checked: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos WHERE id <= ?")
+            .bind(last_photo_id)
+            .fetch_one(&self.primary_db)
+            .await?;
+        let mut progress = ScrubProgress {
+            photos_checked: photos_checked as u64,
+            photos_total: photos_total as u64,
+            issues_found: 0,
+            current_path: None,
+        };
+        let mut summary = ScrubSummary::default();
+        let mut throttle = Throttle::new();
+        let mut last_photo_id = last_photo_id;
+
+        'batches: loop {
+            let batch = sqlx::query_as::<_, CataloguedFile>(
+                "SELECT id, path, file_hash, file_size, file_mtime FROM photos WHERE id > ? ORDER BY id LIMIT ?",
+            )
+            .bind(last_photo_id)
+            .bind(SCRUB_BATCH_SIZE)
+            .fetch_all(&self.primary_db)
+            .await?;
+            if batch.is_empty() {
+                break;
+            }
+            for photo in batch {
+                if cancel.load(Ordering::Relaxed) {
+                    summary.cancelled = true;
+                    break 'batches;
+                }
+                progress.current_path = Some(photo.path.clone());
+                on_progress(&progress);
+
+                let mut roots = vec![(DriveRole::Primary, &self.primary_root)];
+                roots.extend(self.backup_root.as_ref().map(|root| (DriveRole::Backup, root)));
+                for (drive, root) in roots {
+                    match self.check_photo(&photo, drive, root).await {
+                        Ok(Some(status)) if status != FileStatus::Ok => progress.issues_found += 1,
+                        Ok(_) => {}
+                        Err(e) => warn!("Failed to check {} on the {:?} drive: {}", photo.path, drive, e),
+                    }
+                    throttle.wait(library_path::resolve(root, &photo.path).ok().as_deref()).await;
+                }
+
+                last_photo_id = photo.id;
+                sqlx::query("UPDATE scrub_state SET last_photo_id = ?")
+                    .bind(last_photo_id)
+                    .execute(&self.primary_db)
+                    .await?;
+                progress.photos_checked += 1;
+                summary.photos_checked += 1;
+            }
+        }
+
+        if !summary.cancelled {
+            sqlx::query("UPDATE scrub_state SET last_photo_id = 0, finished_at = CURRENT_TIMESTAMP")
+                .execute(&self.primary_db)
+                .await?;
+            info!("Integrity scrub finished, {} files failed their check", progress.issues_found);
+        }
+        progress.current_path = None;
+        on_progress(&progress);
+        summary.issues = Self::issues(&self.primary_db).await?;
+        Ok(summary)
+    }
+
+    /// Checks one copy of a photo and records the outcome. Returns `None`
+    /// when the photo was moved or deleted while it was being checked.
+    ///
+    /// The catalog describes the primary's files, so a primary copy whose
+    /// size or modification time moved on since it was catalogued is
+    /// reported as modified rather than corrupt.
+    async fn check_photo(&self, photo: &CataloguedFile, drive: DriveRole, root: &Path) -> Result<Option<FileStatus>> {
+        let mut status = integrity::check_file(root, &photo.path, &photo.file_hash).await?;
+        if status != FileStatus::Ok {
+            let current: Option<String> = sqlx::query_scalar("SELECT path FROM photos WHERE id = ?")
+                .bind(photo.id)
+                .fetch_optional(&self.primary_db)
+                .await?;
+            if current.as_deref() != Some(photo.path.as_str()) {
+                return Ok(None);
+            }
+            let primary_corrupt = status == FileStatus::Corrupt && drive == DriveRole::Primary;
+            if primary_corrupt && photo.changed_since_catalogued(root).await? {
+                status = FileStatus::Modified;
+            }
+            warn!("{} on the {:?} drive is {}", photo.path, drive, status.as_str());
+        }
+        integrity::record_check(&self.primary_db, photo.id, drive, status).await?;
+        Ok(Some(status))
+    }
+
+    /// Files that failed their last check.
+    pub async fn issues(pool: &SqlitePool) -> Result<Vec<ScrubIssue>> {
+        let issues = sqlx::query_as::<_, ScrubIssue>(
+            "SELECT v.photo_id, p.path, v.drive, v.status,
+                    EXISTS (SELECT 1 FROM photo_verifications o
+                            WHERE o.photo_id = v.photo_id AND o.drive != v.drive AND o.status = 'ok')
+                        AND v.status != 'modified' AS repairable
+             FROM photo_verifications v JOIN photos p ON p.id = v.photo_id
+             WHERE v.status != 'ok'
+             ORDER BY v.photo_id, v.drive",
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(issues)
+    }
+
+    /// Replaces the bad copy of a photo with the copy on the other drive,
+    /// after checking that copy again. A primary copy that was changed after
+    /// it was catalogued is never overwritten.
+    pub async fn repair(&self, photo_id: i64) -> Result<()> {
+        let issue = Self::issues(&self.primary_db)
+            .await?
+            .into_iter()
+            .find(|issue| issue.photo_id == photo_id)
+            .ok_or_else(|| PhotoVaultError::not_found(format!("Integrity issue for photo {}", photo_id)))?;
+        if issue.status == FileStatus::Modified {
+            return Err(PhotoVaultError::conflict(format!(
+                "{} changed after it was catalogued, rescan the library instead",
+                issue.path
+            )));
+        }
+        if !issue.repairable {
+            return Err(PhotoVaultError::conflict(format!("{} has no good copy to repair it from", issue.path)));
+        }
+        let backup_root = self
+            .backup_root
+            .as_ref()
+            .ok_or(PhotoVaultError::DriveOffline { drive: DriveRole::Backup })?;
+        let (good, bad) = match issue.drive {
+            DriveRole::Primary => ((DriveRole::Backup, backup_root), (DriveRole::Primary, &self.primary_root)),
+            DriveRole::Backup => ((DriveRole::Primary, &self.primary_root), (DriveRole::Backup, backup_root)),
+        };
+        let photo = sqlx::query_as::<_, CataloguedFile>(
+            "SELECT id, path, file_hash, file_size, file_mtime FROM photos WHERE id = ?",
+        )
+        .bind(photo_id)
+        .fetch_one(&self.primary_db)
+        .await?;
+        let file_hash = photo.file_hash.as_str();
+        // Backup copies carry the time they were copied, not the original's.
+        if bad.0 == DriveRole::Primary && photo.newer_than_catalogued(bad.1).await? {
+            return Err(PhotoVaultError::conflict(format!(
+                "{} changed after it was catalogued, rescan the library instead",
+                issue.path
+            )));
+        }
+
+        if integrity::check_file(good.1, &issue.path, file_hash).await? != FileStatus::Ok {
+            integrity::record_check(&self.primary_db, photo_id, good.0, FileStatus::Corrupt).await?;
+            return Err(PhotoVaultError::ChecksumMismatch {
+                drive: good.0,
+                path: issue.path,
+            });
+        }
+        let scratch = bad.1.join(".photovault").join("scrub");
+        integrity::recopy(
+            &library_path::resolve(good.1, &issue.path)?,
+            &library_path::resolve(bad.1, &issue.path)?,
+            &scratch,
+        )
+        .await?;
+        let _ = tokio::fs::remove_dir(&scratch).await;
+        if integrity::check_file(bad.1, &issue.path, file_hash).await? != FileStatus::Ok {
+            return Err(PhotoVaultError::ChecksumMismatch {
+                drive: bad.0,
+                path: issue.path,
+            });
+        }
+        info!("Repaired {} on the {:?} drive", issue.path, bad.0);
+        integrity::record_verification(&self.primary_db, photo_id, good.0).await?;
+        integrity::record_verification(&self.primary_db, photo_id, bad.0).await
+    }
+}
+
+/// A photo's file as the catalog last saw it.
+#[derive(sqlx::FromRow)]
+struct CataloguedFile {
+    id: i64,
+    path: String,
+    file_hash: String,
+    file_size: Option<i64>,
+    file_mtime: Option<i64>,
+}
+
+impl CataloguedFile {
+    /// Whether the file under `root` differs in size or modification time
+    /// from what was catalogued.
+    async fn changed_since_catalogued(&self, root: &Path) -> Result<bool> {
+        let Some(metadata) = self.metadata(root).await? else {
+            return Ok(false);
+        };
+        let size_changed = self.file_size.is_some_and(|size| size != metadata.len() as i64);
+        let mtime_changed = self.file_mtime.is_some_and(|mtime| mtime != file_ops::modified_millis(&metadata));
+        Ok(size_changed || mtime_changed)
+    }
+
+    /// Whether the file under `root` was modified after it was catalogued.
+    async fn newer_than_catalogued(&self, root: &Path) -> Result<bool> {
+        let Some(metadata) = self.metadata(root).await? else {
+            return Ok(false);
+        };
+        Ok(self.file_mtime.is_some_and(|mtime| file_ops::modified_millis(&metadata) > mtime))
+    }
+
+    async fn metadata(&self, root: &Path) -> Result<Option<std::fs::Metadata>> {
+        let file = library_path::resolve(root, &self.path)?;
+        match tokio::fs::metadata(&file).await {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(PhotoVaultError::io(&file, e)),
+        }
+    }
+}
+
+/// Paces hashing to `SCRUB_BYTES_PER_SECOND`.
+struct Throttle {
+    started: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Accounts for a file that was just read and sleeps if reading is
+    /// ahead of the allowed rate.
+    async fn wait(&mut self, file: Option<&Path>) {
+        let Some(file) = file else {
+            return;
+        };
+        self.bytes += tokio::fs::metadata(file).await.map(|m| m.len()).unwrap_or(0);
+        let allowed = Duration::from_secs_f64(self.bytes as f64 / SCRUB_BYTES_PER_SECOND);
+        if let Some(ahead) = allowed.checked_sub(self.started.elapsed()) {
+            tokio::time::sleep(ahead).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_scrub_finds_corrupt_copy_and_repairs_it_from_the_other_drive() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = crate::db::init_db(&db_dir.path().join("test.db"), &migrations).await.unwrap();
+        for name in ["a.jpg", "b.jpg"] {
+            for root in [primary.path(), backup.path()] {
+                std::fs::write(root.join(name), name).unwrap();
+            }
+            let hash = integrity::hash_file(&primary.path().join(name)).await.unwrap();
+            sqlx::query("INSERT INTO photos (path, filename, file_hash) VALUES (?, ?, ?)")
+                .bind(name)
+                .bind(name)
+                .bind(hash)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        std::fs::write(backup.path().join("b.jpg"), "rotten").unwrap();
+
+        let scrub = ScrubService::new(pool.clone(), primary.path().to_path_buf(), Some(backup.path().to_path_buf()));
+        assert!(ScrubService::is_due(&pool).await.unwrap());
+        // A pass that was stopped after the first photo resumes after it.
+        sqlx::query("UPDATE scrub_state SET last_photo_id = 1").execute(&pool).await.unwrap();
+        let summary = scrub.run(Arc::new(AtomicBool::new(false)), Arc::new(|_: &ScrubProgress| {})).await.unwrap();
+        assert_eq!(summary.photos_checked, 1);
+        assert_eq!(summary.issues.len(), 1);
+        let issue = &summary.issues[0];
+        assert_eq!((issue.path.as_str(), issue.drive, issue.status), ("b.jpg", DriveRole::Backup, FileStatus::Corrupt));
+        assert!(issue.repairable);
+        assert!(!ScrubService::is_due(&pool).await.unwrap());
+
+        scrub.repair(issue.photo_id).await.unwrap();
+        assert_eq!(std::fs::read(backup.path().join("b.jpg")).unwrap(), b"b.jpg");
+        assert!(ScrubService::issues(&pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scrub_reports_an_edited_file_as_modified_and_will_not_repair_it() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = crate::db::init_db(&db_dir.path().join("test.db"), &migrations).await.unwrap();
+        for root in [primary.path(), backup.path()] {
+            std::fs::write(root.join("a.jpg"), "original").unwrap();
+        }
+        let file = primary.path().join("a.jpg");
+        let metadata = std::fs::metadata(&file).unwrap();
+        sqlx::query("INSERT INTO photos (path, filename, file_hash, file_size, file_mtime) VALUES (?, ?, ?, ?, ?)")
+            .bind("a.jpg")
+            .bind("a.jpg")
+            .bind(integrity::hash_file(&file).await.unwrap())
+            .bind(metadata.len() as i64)
+            .bind(file_ops::modified_millis(&metadata))
+            .execute(&pool)
+            .await
+            .unwrap();
+        std::fs::write(&file, "edited in another app").unwrap();
+
+        let scrub = ScrubService::new(pool.clone(), primary.path().to_path_buf(), Some(backup.path().to_path_buf()));
+        let summary = scrub.run(Arc::new(AtomicBool::new(false)), Arc::new(|_: &ScrubProgress| {})).await.unwrap();
+        assert_eq!(summary.issues.len(), 1);
+        let issue = &summary.issues[0];
+        assert_eq!((issue.drive, issue.status), (DriveRole::Primary, FileStatus::Modified));
+        assert!(!issue.repairable);
+
+        assert!(matches!(scrub.repair(issue.photo_id).await, Err(PhotoVaultError::Conflict { .. })));
+        assert_eq!(std::fs::read(&file).unwrap(), b"edited in another app");
+    }
+}
@@ -0,0 +1,145 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+/// Number of bits in a perceptual hash.
+pub const HASH_BITS: u32 = 64;
+
+/// Difference hash of an image: each bit says whether a pixel of a 9x8
+/// grayscale copy is brighter than its right-hand neighbour. Resizing,
+/// recompressing and small edits flip few bits, so similar images have
+/// hashes a small Hamming distance apart.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Similarity from 0 to 1 of two hashes, 1 meaning identical.
+pub fn similarity(a: u64, b: u64) -> f32 {
+    1.0 - distance(a, b) as f32 / HASH_BITS as f32
+}
+
+/// The largest distance between hashes whose similarity is at least
+/// `threshold`.
+pub fn max_distance(threshold: f32) -> u32 {
+    ((1.0 - threshold.clamp(0.0, 1.0)) * HASH_BITS as f32).floor() as u32
+}
+
+/// Burkhard-Keller tree over perceptual hashes. Finding every hash within
+/// a distance of a query only visits the subtrees the triangle inequality
+/// allows, instead of comparing against every hash.
+pub struct BkTree<T> {
+    root: Option<Node<T>>,
+}
+
+struct Node<T> {
+    hash: u64,
+    value: T,
+    /// Children keyed by their distance to this node.
+    children: Vec<(u32, Node<T>)>,
+}
+
+impl<T> Default for BkTree<T> {
+    fn default() -> Self {
+        Self { root: None }
+    }
+}
+
+impl<T> BkTree<T> {
+    pub fn insert(&mut self, hash: u64, value: T) {
+        let mut node = match &mut self.root {
+            Some(root) => root,
+            None => {
+                self.root = Some(Node {
+                    hash,
+                    value,
+                    children: Vec::new(),
+                });
+                return;
+            }
+        };
+        loop {
+            let d = distance(node.hash, hash);
+            match node.children.iter().position(|(child_distance, _)| *child_distance == d) {
+                Some(index) => node = &mut node.children[index].1,
+                None => {
+                    node.children.push((
+                        d,
+                        Node {
+                            hash,
+                            value,
+                            children: Vec::new(),
+                        },
+                    ));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Values whose hash is at most `max_distance` from `hash`.
+    pub fn find(&self, hash: u64, max_distance: u32) -> Vec<&T> {
+        let mut found = Vec::new();
+        let mut pending: Vec<&Node<T>> = self.root.iter().collect();
+        while let Some(node) = pending.pop() {
+            let d = distance(node.hash, hash);
+            if d <= max_distance {
+                found.push(&node.value);
+            }
+            let range = d.saturating_sub(max_distance)..=d + max_distance;
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|(child_distance, _)| range.contains(child_distance))
+                    .map(|(_, child)| child),
+            );
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_resaved_copies_hash_close_and_tree_finds_them() {
+        let original = DynamicImage::ImageRgb8(RgbImage::from_fn(120, 80, |x, y| {
+            Rgb([(x * 2) as u8, (y * 3) as u8, ((x + y) % 256) as u8])
+        }));
+        // A smaller JPEG re-save, as messaging apps do.
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, 40)
+            .encode_image(&original.resize(60, 40, FilterType::Triangle))
+            .unwrap();
+        let resaved = image::load_from_memory(&jpeg).unwrap();
+        let other = DynamicImage::ImageRgb8(RgbImage::from_fn(120, 80, |x, y| {
+            Rgb([255 - (x * 2) as u8, ((x * y) % 256) as u8, (y * 3) as u8])
+        }));
+
+        let (a, b, c) = (dhash(&original), dhash(&resaved), dhash(&other));
+        assert!(similarity(a, b) >= 0.9, "re-save is {} similar", similarity(a, b));
+        assert!(similarity(a, c) < 0.9);
+
+        let mut tree = BkTree::default();
+        for (hash, name) in [(a, "original"), (c, "other")] {
+            tree.insert(hash, name);
+        }
+        assert_eq!(tree.find(b, max_distance(0.9)), vec![&"original"]);
+        assert_eq!(tree.find(b, HASH_BITS).len(), 2);
+    }
+}
@@ -1,37 +1,102 @@
-use sqlx::SqlitePool;
-use crate::models::operation::Operation;
+use crate::error::{PhotoVaultError, Result};
+use crate::models::drive::DriveRole;
+use sqlx::{SqliteConnection, SqlitePool};
+use crate::models::operation::{Operation, QueuedOperation};
+use log::{debug, info, warn};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use uuid::Uuid;
 use crate::services::album::AlbumService;
+use crate::services::drive_space;
+use crate::services::file_ops::FileOperationService;
+use crate::services::integrity;
+use crate::services::library_path;
 
 pub struct SyncEngine {
     pub primary_db: SqlitePool,
     pub backup_db: Option<SqlitePool>,
-    pub operation_queue: Vec<Operation>,
+    pub primary_root: Option<PathBuf>,
+    pub backup_root: Option<PathBuf>,
+    pub operation_queue: Vec<QueuedOperation>,
+    /// Set while a restore or mirror rewrites a drive outside the journal.
+    suspended: Arc<AtomicBool>,
+}
+
+/// Keeps the sync engine refusing operations until dropped, which also
+/// happens when the task holding it panics.
+pub struct Suspension(Arc<AtomicBool>);
+
+impl Drop for Suspension {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
 }
 
 impl SyncEngine {
-    pub fn new(primary_db: SqlitePool, backup_db: Option<SqlitePool>) -> Self {
+    pub fn new(
+        primary_db: SqlitePool,
+        backup_db: Option<SqlitePool>,
+        primary_root: Option<PathBuf>,
+        backup_root: Option<PathBuf>,
+    ) -> Self {
         Self {
             primary_db,
             backup_db,
+            primary_root,
+            backup_root,
             operation_queue: Vec::new(),
+            suspended: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Refuses operations and queue flushes until the returned guard is
+    /// dropped, so a restore or mirror does not race them on the drives and
+    /// catalogs it rewrites.
+    pub fn suspend(&self) -> Result<Suspension> {
+        if self.suspended.swap(true, Ordering::SeqCst) {
+            return Err(PhotoVaultError::conflict("A restore or mirror is already running"));
+        }
+        Ok(Suspension(self.suspended.clone()))
+    }
+
+    fn ensure_not_suspended(&self) -> Result<()> {
+        if self.suspended.load(Ordering::SeqCst) {
+            return Err(PhotoVaultError::conflict("Changes are paused while a restore or mirror is running"));
+        }
+        Ok(())
+    }
+
+    pub fn attach_backup(&mut self, backup_root: PathBuf, backup_db: SqlitePool) {
+        self.backup_root = Some(backup_root);
+        self.backup_db = Some(backup_db);
+    }
+
+    /// Closes the backup catalog. Later operations are queued until the drive
+    /// is attached again.
+    pub async fn detach_backup(&mut self) {
+        if let Some(backup_db) = self.backup_db.take() {
+            backup_db.close().await;
         }
     }
 
-    pub async fn log_operation(&self, op: &Operation) -> Result<String, sqlx::Error> {
+    pub async fn log_operation(&self, op: &Operation) -> Result<String> {
         let op_id = Uuid::new_v4().to_string();
         let op_type = match op {
             Operation::Move { .. } => "move",
             Operation::Delete { .. } => "delete",
             Operation::Rename { .. } => "rename",
+            Operation::Import { .. } => "import",
             Operation::CreateAlbum { .. } => "create_album",
             Operation::AddToAlbum { .. } => "add_to_album",
             Operation::AddTag { .. } => "add_tag",
+            Operation::MergeLabels { .. } => "merge_labels",
         };
         let params = serde_json::to_string(op).unwrap_or_default();
 
         sqlx::query(
-            "INSERT INTO sync_operations (id, operation_type, params) VALUES (?, ?, ?)",
+            "INSERT INTO sync_operations (id, operation_type, params, status) VALUES (?, ?, ?, 'preparing')",
         )
         .bind(&op_id)
         .bind(op_type)
@@ -42,91 +107,907 @@ impl SyncEngine {
         Ok(op_id)
     }
 
-    pub async fn execute_operation(&mut self, op: Operation) -> Result<(), String> {
-        let op_id = self.log_operation(&op).await.map_err(|e| e.to_string())?;
-        
-        if self.backup_db.is_some() {
-            self.execute_on_both(&op).await?;
-        } else {
-            self.handle_backup_disconnected(op).await?;
-        }
-
-        // Here we would update the status of the operation in the database
-        sqlx::query("UPDATE sync_operations SET status = 'completed' WHERE id = ?")
+    /// Records the phase or outcome of a journaled operation:
+    /// - `preparing` / `prepared`: files are being staged; nothing is applied.
+    /// - `committing`: the operation is going ahead on the primary.
+    /// - `pending`: applied on the primary, the backup still owes it.
+    /// - `failed`: replaying it on the backup failed; it stays queued.
+    /// - `completed`: applied on both drives.
+    /// - `aborted`: rolled back, it never took effect on either drive.
+    async fn set_operation_status(&self, op_id: &str, status: &str, error: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE sync_operations SET status = ?, error_message = ? WHERE id = ?")
+            .bind(status)
+            .bind(error)
             .bind(op_id)
             .execute(&self.primary_db)
-            .await
-            .map_err(|e| e.to_string())?;
-
+            .await?;
         Ok(())
     }
 
-    async fn execute_on_primary(&self, op: &Operation) -> Result<(), sqlx::Error> {
-        let album_service = AlbumService::new(self.primary_db.clone());
-        match op {
-            Operation::CreateAlbum { name } => {
-                album_service.create_album(name.clone()).await?;
-            }
-            Operation::AddToAlbum { photo_id, album_id } => {
-                album_service.add_photos_to_album(vec![*photo_id], *album_id).await?;
-            }
-            // ... other operations
-            _ => {
-                println!("Executing on primary: {:?}", op);
+    /// Restores the offline queue from the journal: every operation that the
+    /// primary applied but the backup has not, in the order it was logged.
+    pub async fn load_pending_operations(&mut self) -> Result<usize> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT id, params FROM sync_operations WHERE status IN ('pending', 'failed') ORDER BY rowid",
+        )
+        .fetch_all(&self.primary_db)
+        .await?;
+
+        self.operation_queue.clear();
+        for (id, params) in rows {
+            match serde_json::from_str::<Operation>(&params) {
+                Ok(operation) => self.operation_queue.push(QueuedOperation { id, operation }),
+                Err(e) => {
+                    warn!("Discarding unreadable operation {}: {}", id, e);
+                    self.set_operation_status(&id, "aborted", Some(&e.to_string())).await?;
+                }
             }
         }
-        Ok(())
+        Ok(self.operation_queue.len())
     }
 
-    async fn execute_on_backup(&self, op: &Operation) -> Result<(), sqlx::Error> {
-        if let Some(backup_db) = &self.backup_db {
-            let album_service = AlbumService::new(backup_db.clone());
-            match op {
-                Operation::CreateAlbum { name } => {
-                    album_service.create_album(name.clone()).await?;
+    /// Finishes or undoes operations that were in flight when the app last
+    /// stopped. Operations that reached `committing` are completed on the
+    /// primary and queued for the backup; earlier ones are rolled back.
+    pub async fn recover_interrupted_operations(&mut self) -> Result<usize> {
+        let Some(primary_root) = self.primary_root.clone() else {
+            return Ok(0);
+        };
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT id, params, status FROM sync_operations
+             WHERE status IN ('preparing', 'prepared', 'committing') ORDER BY rowid",
+        )
+        .fetch_all(&self.primary_db)
+        .await?;
+
+        let recovered = rows.len();
+        for (id, params, status) in rows {
+            let operation = match serde_json::from_str::<Operation>(&params) {
+                Ok(operation) => operation,
+                Err(e) => {
+                    self.set_operation_status(&id, "aborted", Some(&e.to_string())).await?;
+                    continue;
                 }
-                Operation::AddToAlbum { photo_id, album_id } => {
-                    album_service.add_photos_to_album(vec![*photo_id], *album_id).await?;
+            };
+            if status == "committing" {
+                match commit_on_drive(&self.primary_db, &primary_root, &id, &operation).await {
+                    Ok(()) => {
+                        info!("Finished interrupted operation {} on the primary drive", id);
+                        self.set_operation_status(&id, "pending", None).await?;
+                        continue;
+                    }
+                    Err(e) => warn!("Failed to finish interrupted operation {}: {}", id, e),
                 }
-                // ... other operations
-                _ => {
-                    println!("Executing on backup: {:?}", op);
+            }
+            info!("Rolling back interrupted operation {}", id);
+            self.set_operation_status(&id, "aborted", Some("Interrupted before commit")).await?;
+        }
+
+        // Whatever is still staged belongs to an operation that did not go
+        // ahead, so it is put back where it came from.
+        self.restore_staging(&primary_root).await?;
+        Ok(recovered)
+    }
+
+    /// Moves files left in a drive's staging area back to their original
+    /// location, using the journal to find out where that was.
+    async fn restore_staging(&self, root: &Path) -> Result<()> {
+        let staging = staging_root(root);
+        let mut entries = match tokio::fs::read_dir(&staging).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(PhotoVaultError::io(&staging, e)),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let op_id = entry.file_name().to_string_lossy().into_owned();
+            let params: Option<String> = sqlx::query_scalar("SELECT params FROM sync_operations WHERE id = ?")
+                .bind(&op_id)
+                .fetch_optional(&self.primary_db)
+                .await?;
+            match params.and_then(|params| serde_json::from_str::<Operation>(&params).ok()) {
+                Some(operation) => {
+                    if let Err(e) = abort_on_drive(root, &op_id, &operation).await {
+                        warn!("Failed to restore staged files of {}: {}", op_id, e);
+                    }
                 }
+                None => warn!("Leaving unknown staged files in {}", entry.path().display()),
             }
         }
         Ok(())
     }
 
-    pub async fn execute_on_both(&mut self, op: &Operation) -> Result<(), String> {
-        if let Err(e) = self.execute_on_primary(op).await {
-            return Err(format!("Failed to execute on primary: {}", e));
+    pub async fn execute_operation(&mut self, op: Operation) -> Result<()> {
+        self.ensure_not_suspended()?;
+        let op_id = self.log_operation(&op).await?;
+        self.execute_on_both(QueuedOperation { id: op_id, operation: op }).await
+    }
+
+    fn attached_backup(&self) -> Option<(SqlitePool, PathBuf)> {
+        Some((self.backup_db.clone()?, self.backup_root.clone()?))
+    }
+
+    /// Applies a journaled operation to both drives in two phases. Both drives
+    /// first stage the change; if the backup refuses it, the primary is rolled
+    /// back and the operation fails. Only then is it committed on each drive.
+    /// A backup that is offline, or drops out midway, gets the operation
+    /// queued instead.
+    pub async fn execute_on_both(&mut self, queued: QueuedOperation) -> Result<()> {
+        let (op_id, op) = (&queued.id, &queued.operation);
+        let Some(primary_root) = self.primary_root.clone() else {
+            let e = PhotoVaultError::DriveOffline { drive: DriveRole::Primary };
+            self.set_operation_status(op_id, "aborted", Some(&e.to_string())).await?;
+            return Err(e);
+        };
+
+        // Phase 1: prepare, after making sure each drive has room for it.
+        let prepared = match ensure_space_for(&primary_root, DriveRole::Primary, op, None).await {
+            Ok(()) => prepare_on_drive(&primary_root, op_id, op, None).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = prepared {
+            self.roll_back(op_id, op, &[&primary_root], &e).await?;
+            return Err(e);
+        }
+        let mut backup = self.attached_backup();
+        if let Some((_, backup_root)) = &backup {
+            let prepared = match ensure_space_for(backup_root, DriveRole::Backup, op, Some(&primary_root)).await {
+                Ok(()) => prepare_on_drive(backup_root, op_id, op, Some(&primary_root)).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = prepared {
+                if backup_root.is_dir() {
+                    self.roll_back(op_id, op, &[backup_root, &primary_root], &e).await?;
+                    return Err(e);
+                }
+                // The drive went away; that is not a reason to refuse.
+                warn!("Backup drive went offline while preparing: {}", e);
+                backup = None;
+            }
         }
 
-        if let Some(backup_db) = &self.backup_db {
-            if let Err(e) = self.execute_on_backup(op).await {
-                println!("Failed to execute on backup: {}. Queuing operation.", e);
-                self.operation_queue.push(op.clone());
+        // Phase 2: commit.
+        self.set_operation_status(op_id, "committing", None).await?;
+        if let Err(e) = commit_on_drive(&self.primary_db, &primary_root, op_id, op).await {
+            let mut roots = vec![&primary_root];
+            roots.extend(backup.as_ref().map(|(_, root)| root));
+            self.roll_back(op_id, op, &roots, &e).await?;
+            return Err(e);
+        }
+        if let Some((backup_db, backup_root)) = &backup {
+            match commit_on_drive(backup_db, backup_root, op_id, op).await {
+                Ok(()) => match self.verify_on_backup(backup_db, backup_root, op_id, op).await {
+                    Ok(()) => return self.set_operation_status(op_id, "completed", None).await,
+                    Err(e) => {
+                        warn!("Backup copy failed verification: {}. Queuing operation.", e);
+                        self.set_operation_status(op_id, "failed", Some(&e.to_string())).await?;
+                        return self.handle_backup_disconnected(queued).await;
+                    }
+                },
+                // The primary has already changed, so the backup catches up
+                // on the next flush instead.
+                Err(e) => {
+                    warn!("Failed to commit on backup: {}. Queuing operation.", e);
+                    if let Err(e) = abort_on_drive(backup_root, op_id, op).await {
+                        warn!("Failed to clean up backup staging: {}", e);
+                    }
+                }
             }
-        } else {
-            self.operation_queue.push(op.clone());
         }
+        self.set_operation_status(op_id, "pending", None).await?;
+        self.handle_backup_disconnected(queued).await
+    }
 
+    /// Marks queued operations done without replaying them, once the backup
+    /// was brought in line with the primary by other means.
+    pub async fn settle_queued(&mut self, op_ids: &[String]) -> Result<()> {
+        for op_id in op_ids {
+            self.set_operation_status(op_id, "completed", Some("Superseded by mirroring the primary"))
+                .await?;
+        }
+        self.operation_queue.retain(|queued| !op_ids.contains(&queued.id));
         Ok(())
     }
 
-    pub async fn handle_backup_disconnected(&mut self, op: Operation) -> Result<(), String> {
-        println!("Backup disconnected. Queuing operation: {:?}", op);
-        self.operation_queue.push(op);
+    /// Undoes the staging of `op` on the given drives and marks it aborted.
+    async fn roll_back(&self, op_id: &str, op: &Operation, roots: &[&PathBuf], reason: &PhotoVaultError) -> Result<()> {
+        for root in roots {
+            if let Err(e) = abort_on_drive(root, op_id, op).await {
+                warn!("Failed to roll back {} on {}: {}", op_id, root.display(), e);
+            }
+        }
+        self.set_operation_status(op_id, "aborted", Some(&reason.to_string())).await
+    }
+
+    /// Checks a file that `op` copied onto the backup against the hash the
+    /// primary recorded for it, copying it again if it does not match. Only
+    /// imports bring bytes over from another drive; other operations move
+    /// files within the backup, and a move done as copy and delete checks
+    /// its copy in `copy_verified`.
+    async fn verify_on_backup(&self, backup_db: &SqlitePool, backup_root: &Path, op_id: &str, op: &Operation) -> Result<()> {
+        let (Operation::Import { path, .. }, Some(primary_root)) = (op, &self.primary_root) else {
+            return Ok(());
+        };
+        let scratch = staging_root(backup_root).join(op_id);
+        let verified =
+            integrity::verify_backup_copy(&self.primary_db, primary_root, backup_db, backup_root, path, &scratch).await;
+        remove_staging_dir(backup_root, op_id).await;
+        verified
+    }
+
+    pub async fn handle_backup_disconnected(&mut self, queued: QueuedOperation) -> Result<()> {
+        info!("Backup disconnected. Queuing operation: {:?}", queued.operation);
+        self.operation_queue.push(queued);
         Ok(())
     }
 
-    pub async fn flush_queue(&mut self) -> Result<(), String> {
-        println!("Flushing operation queue...");
-        let ops: Vec<Operation> = self.operation_queue.drain(..).collect();
-        for op in ops {
-            self.execute_on_both(&op).await?;
+    /// Replays queued operations on the backup in the order they were logged.
+    /// Operations that fail are marked `failed` and kept for the next flush.
+    pub async fn flush_queue(&mut self) -> Result<()> {
+        self.ensure_not_suspended()?;
+        let Some((backup_db, backup_root)) = self.attached_backup() else {
+            return Ok(());
+        };
+        // Files staged by an operation that was cut short go back first, so
+        // the replay starts from the drive's last committed state.
+        self.restore_staging(&backup_root).await?;
+
+        info!("Flushing {} queued operations...", self.operation_queue.len());
+        let queued: Vec<QueuedOperation> = self.operation_queue.drain(..).collect();
+        let primary_root = self.primary_root.clone();
+        for entry in queued {
+            let (op_id, op) = (&entry.id, &entry.operation);
+            let result = match ensure_space_for(&backup_root, DriveRole::Backup, op, primary_root.as_deref()).await {
+                Ok(()) => match prepare_on_drive(&backup_root, op_id, op, primary_root.as_deref()).await {
+                    Ok(()) => match commit_on_drive(&backup_db, &backup_root, op_id, op).await {
+                        Ok(()) => self.verify_on_backup(&backup_db, &backup_root, op_id, op).await,
+                        Err(e) => Err(e),
+                    },
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => self.set_operation_status(&entry.id, "completed", None).await?,
+                Err(e) => {
+                    warn!("Failed to replay operation {} on backup: {}", entry.id, e);
+                    if let Err(e) = abort_on_drive(&backup_root, &entry.id, &entry.operation).await {
+                        warn!("Failed to clean up backup staging: {}", e);
+                    }
+                    self.set_operation_status(&entry.id, "failed", Some(&e.to_string())).await?;
+                    self.operation_queue.push(entry);
+                }
+            }
         }
-        println!("Operation queue flushed.");
+        info!("Operation queue flushed, {} operations remain.", self.operation_queue.len());
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn staging_root(root: &Path) -> PathBuf {
+    root.join(".photovault").join("staging")
+}
+
+/// Where a file taken out of the library by operation `op_id` is kept until
+/// the operation is committed or aborted. It is on the same drive, so moving
+/// files in and out of it is a rename.
+fn staged_file(root: &Path, op_id: &str, source: &str) -> PathBuf {
+    staging_root(root)
+        .join(op_id)
+        .join(source.rsplit('/').next().unwrap_or(source))
+}
+
+/// The library file an operation changes, if it changes one.
+fn source_path(op: &Operation) -> Option<&str> {
+    match op {
+        Operation::Move { from, .. } => Some(from),
+        Operation::Rename { path, .. } | Operation::Delete { path } => Some(path),
+        _ => None,
+    }
+}
+
+/// Where the file ends up once the operation is committed.
+fn target_path(op: &Operation) -> Result<Option<String>> {
+    match op {
+        Operation::Move { to, .. } => Ok(Some(to.clone())),
+        Operation::Rename { path, new_name } => renamed_path(path, new_name).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The file an import copies from. The backup falls back to the primary's
+/// copy, since the original may be gone by the time a queued import is
+/// replayed.
+async fn import_source(source: &str, path: &str, fallback_root: Option<&Path>) -> Result<PathBuf> {
+    if tokio::fs::try_exists(source).await.unwrap_or(false) {
+        return Ok(PathBuf::from(source));
+    }
+    if let Some(fallback_root) = fallback_root {
+        let fallback = library_path::resolve(fallback_root, path)?;
+        if tokio::fs::try_exists(&fallback).await.unwrap_or(false) {
+            return Ok(fallback);
+        }
+    }
+    Err(PhotoVaultError::not_found(source))
+}
+
+/// Bytes an operation writes to the drive at `root`. Moves are counted in
+/// full because a move that cannot be done as a rename is done as a copy.
+async fn required_bytes(root: &Path, op: &Operation, fallback_root: Option<&Path>) -> Result<u64> {
+    let file = match op {
+        Operation::Move { from, .. } => library_path::resolve(root, from)?,
+        Operation::Import { source, path } => match import_source(source, path, fallback_root).await {
+            Ok(file) => file,
+            // Reported by the prepare step.
+            Err(_) => return Ok(0),
+        },
+        _ => return Ok(0),
+    };
+    Ok(tokio::fs::metadata(&file).await.map(|metadata| metadata.len()).unwrap_or(0))
+}
+
+async fn ensure_space_for(root: &Path, drive: DriveRole, op: &Operation, fallback_root: Option<&Path>) -> Result<()> {
+    let required = required_bytes(root, op, fallback_root).await?;
+    drive_space::ensure_space(root, drive, required)
+}
+
+/// Phase 1: moves the affected file into the drive's staging area after
+/// checking that the operation can be applied. A file that is already at
+/// its target, or already deleted, stages nothing; the operation was applied
+/// before and committing only brings the catalog in line. Imports copy their
+/// file into the staging area instead, checking the copy against the
+/// original.
+async fn prepare_on_drive(root: &Path, op_id: &str, op: &Operation, fallback_root: Option<&Path>) -> Result<()> {
+    if let Operation::Import { source, path } = op {
+        let target = library_path::resolve(root, path)?;
+        let from = import_source(source, path, fallback_root).await?;
+        if let Ok(existing) = tokio::fs::metadata(&target).await {
+            // Imported before, e.g. when replaying an operation.
+            let source_len = tokio::fs::metadata(&from).await.map(|m| m.len()).ok();
+            if source_len == Some(existing.len()) {
+                return Ok(());
+            }
+            return Err(PhotoVaultError::conflict(format!("{} already exists", path)));
+        }
+        let staged = staged_file(root, op_id, path);
+        if let Some(parent) = staged.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| PhotoVaultError::io(parent, e))?;
+        }
+        return copy_verified(&from, &staged).await;
+    }
+    let Some(source) = source_path(op) else {
+        return Ok(());
+    };
+    let from = library_path::resolve(root, source)?;
+    let source_exists = tokio::fs::try_exists(&from).await.unwrap_or(false);
+    if let Some(target) = target_path(op)? {
+        let target_exists = tokio::fs::try_exists(library_path::resolve(root, &target)?).await.unwrap_or(false);
+        match (source_exists, target_exists) {
+            (true, false) => {}
+            (true, true) => return Err(PhotoVaultError::conflict(format!("{} already exists", target))),
+            (false, true) => return Ok(()),
+            (false, false) => return Err(PhotoVaultError::not_found(source)),
+        }
+    } else if !source_exists {
+        return Ok(());
+    }
+    place_file(&from, &staged_file(root, op_id, source)).await
+}
+
+/// Phase 2: moves the staged file to its target, or discards it, together
+/// with the matching catalog change.
+async fn commit_on_drive(pool: &SqlitePool, root: &Path, op_id: &str, op: &Operation) -> Result<()> {
+    match op {
+        Operation::Move { .. } | Operation::Rename { .. } => {
+            let source = source_path(op).unwrap_or_default();
+            let target = target_path(op)?.unwrap_or_default();
+            let staged = staged_file(root, op_id, source);
+            let to = library_path::resolve(root, &target)?;
+
+            let mut tx = pool.begin().await?;
+            update_photo_path(&mut tx, source, &target).await?;
+            let placed = tokio::fs::try_exists(&staged).await.unwrap_or(false);
+            if placed {
+                place_file(&staged, &to).await?;
+            }
+            if let Err(e) = tx.commit().await {
+                if placed {
+                    let _ = place_file(&to, &staged).await;
+                }
+                return Err(e.into());
+            }
+        }
+        Operation::Import { path, .. } => {
+            let staged = staged_file(root, op_id, path);
+            let to = library_path::resolve(root, path)?;
+            if tokio::fs::try_exists(&staged).await.unwrap_or(false) {
+                place_file(&staged, &to).await?;
+            }
+            let catalogued = FileOperationService::new(root.to_path_buf(), pool.clone())
+                .catalog_file(&to)
+                .await;
+            if let Err(e) = catalogued {
+                // Back into staging, so aborting discards it.
+                let _ = place_file(&to, &staged).await;
+                return Err(e);
+            }
+        }
+        Operation::Delete { path } => {
+            let mut tx = pool.begin().await?;
+            delete_photo(&mut tx, path).await?;
+            tx.commit().await?;
+            match tokio::fs::remove_file(staged_file(root, op_id, path)).await {
+                Ok(()) => {}
+                // Nothing was staged, e.g. when replaying an operation.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(PhotoVaultError::io(Path::new(path), e)),
+            }
+        }
+        Operation::MergeLabels { from, into } => {
+            let mut tx = pool.begin().await?;
+            merge_labels(&mut tx, from, into).await?;
+            tx.commit().await?;
+        }
+        Operation::CreateAlbum { name } => {
+            AlbumService::new(pool.clone())
+                .create_album(name.clone())
+                .await?;
+        }
+        Operation::AddToAlbum { path, album } => {
+            let (photo_id, album_id) = find_photo_and_album(pool, path, album).await?;
+            AlbumService::new(pool.clone())
+                .add_photos_to_album(vec![photo_id], album_id)
+                .await?;
+        }
+        Operation::AddTag { path, tag_name } => {
+            let mut tx = pool.begin().await?;
+            add_tag(&mut tx, path, tag_name).await?;
+            tx.commit().await?;
+        }
+    }
+    remove_staging_dir(root, op_id).await;
+    debug!("Committed operation {} on {}", op_id, root.display());
+    Ok(())
+}
+
+/// Puts a staged file back where it came from. Files staged by an import
+/// are copies and are discarded.
+async fn abort_on_drive(root: &Path, op_id: &str, op: &Operation) -> Result<()> {
+    if let Operation::Import { path, .. } = op {
+        match tokio::fs::remove_file(staged_file(root, op_id, path)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(PhotoVaultError::io(&staging_root(root).join(op_id), e)),
+        }
+    } else if let Some(source) = source_path(op) {
+        let staged = staged_file(root, op_id, source);
+        if tokio::fs::try_exists(&staged).await.unwrap_or(false) {
+            place_file(&staged, &library_path::resolve(root, source)?).await?;
+        }
+    }
+    remove_staging_dir(root, op_id).await;
+    Ok(())
+}
+
+async fn remove_staging_dir(root: &Path, op_id: &str) {
+    // Only removes the folder once it is empty.
+    let _ = tokio::fs::remove_dir(staging_root(root).join(op_id)).await;
+}
+
+fn renamed_path(path: &str, new_name: &str) -> Result<String> {
+    let is_plain_name = Path::new(new_name).file_name().is_some_and(|name| name == new_name);
+    if !is_plain_name || new_name.contains(['/', '\\']) {
+        return Err(PhotoVaultError::invalid_input(format!("Invalid file name: {}", new_name)));
+    }
+    let folder = path.rsplit_once('/').map_or("", |(folder, _)| folder);
+    Ok(library_path::join(folder, new_name))
+}
+
+/// Moves a file within a drive, creating the destination folder as needed.
+/// Falls back to copy and delete when the rename fails.
+async fn place_file(from: &Path, to: &Path) -> Result<()> {
+    if tokio::fs::try_exists(to).await.unwrap_or(false) {
+        return Err(PhotoVaultError::conflict(format!("{} already exists", to.display())));
+    }
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| PhotoVaultError::io(parent, e))?;
+    }
+    if tokio::fs::rename(from, to).await.is_err() {
+        copy_verified(from, to).await?;
+        tokio::fs::remove_file(from)
+            .await
+            .map_err(|e| PhotoVaultError::io(from, e))?;
+    }
+    Ok(())
+}
+
+/// Copies `from` to `to` and checks the copy against the original. A copy
+/// that does not match is removed.
+async fn copy_verified(from: &Path, to: &Path) -> Result<()> {
+    tokio::fs::copy(from, to)
+        .await
+        .map_err(|e| PhotoVaultError::io(from, e))?;
+    if integrity::hash_file(to).await? != integrity::hash_file(from).await? {
+        let _ = tokio::fs::remove_file(to).await;
+        let mismatch = io::Error::new(io::ErrorKind::InvalidData, "the copy does not match the original");
+        return Err(PhotoVaultError::io(to, mismatch));
+    }
+    Ok(())
+}
+
+async fn update_photo_path(conn: &mut SqliteConnection, from: &str, to: &str) -> Result<()> {
+    let filename = to.rsplit('/').next().unwrap_or(to);
+    sqlx::query("UPDATE photos SET path = ?, filename = ? WHERE path = ?")
+        .bind(to)
+        .bind(filename)
+        .bind(from)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+async fn delete_photo(conn: &mut SqliteConnection, path: &str) -> Result<()> {
+    let photo_id: Option<i64> = sqlx::query_scalar("SELECT id FROM photos WHERE path = ?")
+        .bind(path)
+        .fetch_optional(&mut *conn)
+        .await?;
+    if let Some(photo_id) = photo_id {
+        for table in ["photo_exif", "photo_album", "photo_tag", "photo_verifications"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE photo_id = ?", table))
+                .bind(photo_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        sqlx::query("DELETE FROM photos WHERE id = ?")
+            .bind(photo_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Ids of the photo at `path` and the album named `album` in one drive's
+/// catalog.
+async fn find_photo_and_album(pool: &SqlitePool, path: &str, album: &str) -> Result<(i64, i64)> {
+    let photo_id: i64 = sqlx::query_scalar("SELECT id FROM photos WHERE path = ?")
+        .bind(path)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| PhotoVaultError::not_found(format!("Photo {}", path)))?;
+    let album_id: i64 = sqlx::query_scalar("SELECT id FROM albums WHERE name = ?")
+        .bind(album)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| PhotoVaultError::not_found(format!("Album {}", album)))?;
+    Ok((photo_id, album_id))
+}
+
+async fn add_tag(conn: &mut SqliteConnection, path: &str, tag_name: &str) -> Result<()> {
+    let photo_id: i64 = sqlx::query_scalar("SELECT id FROM photos WHERE path = ?")
+        .bind(path)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| PhotoVaultError::not_found(format!("Photo {}", path)))?;
+    sqlx::query("INSERT OR IGNORE INTO tags (name) VALUES (?)")
+        .bind(tag_name)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("INSERT OR IGNORE INTO photo_tag (photo_id, tag_id) SELECT ?, id FROM tags WHERE name = ?")
+        .bind(photo_id)
+        .bind(tag_name)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn merge_labels(conn: &mut SqliteConnection, from: &str, into: &str) -> Result<()> {
+    for (table, column) in [("photo_album", "album_id"), ("photo_tag", "tag_id")] {
+        sqlx::query(&format!(
+            "INSERT OR IGNORE INTO {0} (photo_id, {1})
+             SELECT keeper.id, link.{1} FROM {0} link
+             JOIN photos source ON source.id = link.photo_id, photos keeper
+             WHERE source.path = ? AND keeper.path = ?",
+            table, column
+        ))
+        .bind(from)
+        .bind(into)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn test_pool(dir: &Path) -> SqlitePool {
+        std::fs::create_dir_all(dir).unwrap();
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        crate::db::init_db(&dir.join("test.db"), &migrations).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_move_and_delete_are_mirrored_to_backup() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let backup_db = test_pool(&db_dir.path().join("backup")).await;
+        for root in [primary.path(), backup.path()] {
+            std::fs::write(root.join("a.jpg"), b"photo").unwrap();
+        }
+        for pool in [&primary_db, &backup_db] {
+            sqlx::query("INSERT INTO photos (path, filename, file_hash) VALUES ('a.jpg', 'a.jpg', 'hash')")
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+
+        let mut engine = SyncEngine::new(
+            primary_db.clone(),
+            Some(backup_db.clone()),
+            Some(primary.path().to_path_buf()),
+            Some(backup.path().to_path_buf()),
+        );
+        // The drives are mounted at different places; the operation is not.
+        engine
+            .execute_operation(Operation::Move { from: "a.jpg".into(), to: "2024/a.jpg".into() })
+            .await
+            .unwrap();
+
+        let moved = primary.path().join("2024").join("a.jpg");
+        assert!(moved.exists());
+        assert!(backup.path().join("2024").join("a.jpg").exists());
+        assert!(!backup.path().join("a.jpg").exists());
+        for pool in [&primary_db, &backup_db] {
+            let path: String = sqlx::query_scalar("SELECT path FROM photos").fetch_one(pool).await.unwrap();
+            assert_eq!(path, "2024/a.jpg");
+        }
+
+        engine.execute_operation(Operation::Delete { path: "2024/a.jpg".into() }).await.unwrap();
+        assert!(!moved.exists());
+        assert!(!backup.path().join("2024").join("a.jpg").exists());
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos").fetch_one(&backup_db).await.unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_add_to_album_finds_rows_by_path_and_name_on_each_drive() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let backup_db = test_pool(&db_dir.path().join("backup")).await;
+        // The same photos and album, catalogued in a different order.
+        for (pool, photos, albums) in [
+            (&primary_db, "(1, 'a.jpg', 'a.jpg', 'a'), (2, 'b.jpg', 'b.jpg', 'b')", "(1, 'Trip')"),
+            (&backup_db, "(1, 'b.jpg', 'b.jpg', 'b'), (2, 'a.jpg', 'a.jpg', 'a')", "(1, 'Other'), (2, 'Trip')"),
+        ] {
+            let photos = format!("INSERT INTO photos (id, path, filename, file_hash) VALUES {}", photos);
+            sqlx::query(&photos).execute(pool).await.unwrap();
+            let albums = format!("INSERT INTO albums (id, name) VALUES {}", albums);
+            sqlx::query(&albums).execute(pool).await.unwrap();
+        }
+
+        let mut engine = SyncEngine::new(
+            primary_db.clone(),
+            Some(backup_db.clone()),
+            Some(primary.path().to_path_buf()),
+            Some(backup.path().to_path_buf()),
+        );
+        engine
+            .execute_operation(Operation::AddToAlbum { path: "a.jpg".into(), album: "Trip".into() })
+            .await
+            .unwrap();
+
+        assert!(engine.operation_queue.is_empty());
+        for (pool, expected) in [(&primary_db, (1, 1)), (&backup_db, (2, 2))] {
+            let row: (i64, i64) = sqlx::query_as("SELECT photo_id, album_id FROM photo_album")
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            assert_eq!(row, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_tag_creates_the_tag_on_both_drives() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let backup_db = test_pool(&db_dir.path().join("backup")).await;
+        for pool in [&primary_db, &backup_db] {
+            sqlx::query("INSERT INTO photos (path, filename, file_hash) VALUES ('a.jpg', 'a.jpg', 'hash')")
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+
+        let mut engine = SyncEngine::new(
+            primary_db.clone(),
+            Some(backup_db.clone()),
+            Some(primary.path().to_path_buf()),
+            Some(backup.path().to_path_buf()),
+        );
+        let tag = || Operation::AddTag { path: "a.jpg".into(), tag_name: "beach".into() };
+        engine.execute_operation(tag()).await.unwrap();
+        // Tagging twice is harmless.
+        engine.execute_operation(tag()).await.unwrap();
+        let missing = Operation::AddTag { path: "gone.jpg".into(), tag_name: "beach".into() };
+        assert!(matches!(engine.execute_operation(missing).await, Err(PhotoVaultError::NotFound { .. })));
+
+        for pool in [&primary_db, &backup_db] {
+            let tags: Vec<String> = sqlx::query_scalar(
+                "SELECT tags.name FROM photo_tag JOIN tags ON tags.id = photo_tag.tag_id
+                 JOIN photos ON photos.id = photo_tag.photo_id WHERE photos.path = 'a.jpg'",
+            )
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            assert_eq!(tags, vec!["beach"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_operations_are_refused_while_suspended() {
+        let (primary, db_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let mut engine = SyncEngine::new(primary_db, None, Some(primary.path().to_path_buf()), None);
+
+        let suspension = engine.suspend().unwrap();
+        assert!(matches!(engine.suspend(), Err(PhotoVaultError::Conflict { .. })));
+        let create = || Operation::CreateAlbum { name: "Trip".into() };
+        assert!(matches!(engine.execute_operation(create()).await, Err(PhotoVaultError::Conflict { .. })));
+        assert!(matches!(engine.flush_queue().await, Err(PhotoVaultError::Conflict { .. })));
+
+        drop(suspension);
+        engine.execute_operation(create()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_offline_operations_are_replayed_after_restart() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let backup_db = test_pool(&db_dir.path().join("backup")).await;
+        for root in [primary.path(), backup.path()] {
+            std::fs::write(root.join("a.jpg"), b"photo").unwrap();
+        }
+
+        // Backup offline: only the primary is renamed.
+        let mut engine = SyncEngine::new(primary_db.clone(), None, Some(primary.path().to_path_buf()), None);
+        engine
+            .execute_operation(Operation::Rename { path: "a.jpg".into(), new_name: "b.jpg".into() })
+            .await
+            .unwrap();
+        assert_eq!(engine.operation_queue.len(), 1);
+        assert!(backup.path().join("a.jpg").exists());
+
+        // After a restart with the backup attached, the queue is rebuilt from
+        // the journal and replayed.
+        let mut engine = SyncEngine::new(
+            primary_db.clone(),
+            Some(backup_db),
+            Some(primary.path().to_path_buf()),
+            Some(backup.path().to_path_buf()),
+        );
+        assert_eq!(engine.load_pending_operations().await.unwrap(), 1);
+        engine.flush_queue().await.unwrap();
+
+        assert!(engine.operation_queue.is_empty());
+        assert!(backup.path().join("b.jpg").exists());
+        let status: String = sqlx::query_scalar("SELECT status FROM sync_operations")
+            .fetch_one(&primary_db)
+            .await
+            .unwrap();
+        assert_eq!(status, "completed");
+    }
+
+    #[tokio::test]
+    async fn test_replayed_create_album_keeps_an_album_that_already_exists() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let backup_db = test_pool(&db_dir.path().join("backup")).await;
+        let mut engine = SyncEngine::new(primary_db.clone(), None, Some(primary.path().to_path_buf()), None);
+        engine.execute_operation(Operation::CreateAlbum { name: "Trip".into() }).await.unwrap();
+        // The backup got the album before the queued operation was completed.
+        sqlx::query("INSERT INTO albums (name) VALUES ('Trip')").execute(&backup_db).await.unwrap();
+
+        let mut engine = SyncEngine::new(
+            primary_db.clone(),
+            Some(backup_db.clone()),
+            Some(primary.path().to_path_buf()),
+            Some(backup.path().to_path_buf()),
+        );
+        assert_eq!(engine.load_pending_operations().await.unwrap(), 1);
+        engine.flush_queue().await.unwrap();
+        engine.execute_operation(Operation::CreateAlbum { name: "Trip".into() }).await.unwrap();
+
+        for pool in [&primary_db, &backup_db] {
+            let albums: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM albums WHERE name = 'Trip'")
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            assert_eq!(albums, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_imported_backup_copy_is_verified_against_primary_hash() {
+        let (primary, backup, db_dir, outside) =
+            (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let backup_db = test_pool(&db_dir.path().join("backup")).await;
+        let source = outside.path().join("a.png");
+        image::RgbImage::new(4, 3).save(&source).unwrap();
+
+        let mut engine = SyncEngine::new(primary_db.clone(), None, Some(primary.path().to_path_buf()), None);
+        engine
+            .execute_operation(Operation::Import {
+                source: source.to_string_lossy().into_owned(),
+                path: "2024/a.png".into(),
+            })
+            .await
+            .unwrap();
+        // The original changes before the backup catches up, so the backup's
+        // first copy does not match what the primary catalogued.
+        image::RgbImage::new(3, 4).save(&source).unwrap();
+
+        engine.attach_backup(backup.path().to_path_buf(), backup_db.clone());
+        engine.flush_queue().await.unwrap();
+
+        assert!(engine.operation_queue.is_empty());
+        let imported = std::fs::read(primary.path().join("2024").join("a.png")).unwrap();
+        assert_eq!(std::fs::read(backup.path().join("2024").join("a.png")).unwrap(), imported);
+        let mut hashes = Vec::new();
+        for pool in [&primary_db, &backup_db] {
+            let hash: String = sqlx::query_scalar("SELECT file_hash FROM photos").fetch_one(pool).await.unwrap();
+            hashes.push(hash);
+        }
+        assert_eq!(hashes[0], hashes[1]);
+        let verified: String = sqlx::query_scalar("SELECT drive FROM photo_verifications")
+            .fetch_one(&primary_db)
+            .await
+            .unwrap();
+        assert_eq!(verified, "backup");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_backup_rolls_back_primary_and_recovery_undoes_staging() {
+        let (primary, backup, db_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let primary_db = test_pool(&db_dir.path().join("primary")).await;
+        let backup_db = test_pool(&db_dir.path().join("backup")).await;
+        std::fs::write(primary.path().join("a.jpg"), b"photo").unwrap();
+        std::fs::write(primary.path().join("b.jpg"), b"photo").unwrap();
+        // The backup already has a different file where the rename would go.
+        std::fs::write(backup.path().join("a.jpg"), b"photo").unwrap();
+        std::fs::write(backup.path().join("c.jpg"), b"other").unwrap();
+
+        let mut engine = SyncEngine::new(
+            primary_db.clone(),
+            Some(backup_db),
+            Some(primary.path().to_path_buf()),
+            Some(backup.path().to_path_buf()),
+        );
+        let result = engine
+            .execute_operation(Operation::Rename { path: "a.jpg".into(), new_name: "c.jpg".into() })
+            .await;
+        assert!(result.is_err());
+        assert!(primary.path().join("a.jpg").exists());
+        assert!(!primary.path().join("c.jpg").exists());
+        assert!(engine.operation_queue.is_empty());
+        let status: String = sqlx::query_scalar("SELECT status FROM sync_operations")
+            .fetch_one(&primary_db)
+            .await
+            .unwrap();
+        assert_eq!(status, "aborted");
+
+        // Simulate a crash after b.jpg was staged for deletion.
+        let operation = Operation::Delete { path: "b.jpg".into() };
+        let op_id = engine.log_operation(&operation).await.unwrap();
+        prepare_on_drive(primary.path(), &op_id, &operation, None).await.unwrap();
+        assert!(!primary.path().join("b.jpg").exists());
+
+        let mut engine = SyncEngine::new(primary_db.clone(), None, Some(primary.path().to_path_buf()), None);
+        assert_eq!(engine.recover_interrupted_operations().await.unwrap(), 1);
+        assert!(primary.path().join("b.jpg").exists());
+        assert!(!staging_root(primary.path()).join(&op_id).exists());
+    }
+}
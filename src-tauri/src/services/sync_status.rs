@@ -1,22 +1,32 @@
+use crate::error::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use crate::services::sync_engine::SyncEngine;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncStatus {
     pub primary_connected: bool,
     pub backup_connected: bool,
-    pub last_sync: DateTime<Utc>,
+    /// When an operation last completed on both drives, if ever.
+    pub last_sync: Option<DateTime<Utc>>,
     pub is_in_sync: bool,
     pub pending_operations: u32,
 }
 
-pub async fn verify_sync_status() -> Result<SyncStatus, String> {
-    // Placeholder implementation
+pub async fn verify_sync_status(engine: &SyncEngine) -> Result<SyncStatus> {
+    let last_sync: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT MAX(timestamp) FROM sync_operations WHERE status = 'completed'")
+            .fetch_one(&engine.primary_db)
+            .await?;
+    let primary_connected = engine.primary_root.as_deref().is_some_and(|root| root.is_dir());
+    let backup_connected = engine.backup_db.is_some();
+    let pending_operations = engine.operation_queue.len() as u32;
+
     Ok(SyncStatus {
-        primary_connected: true,
-        backup_connected: false,
-        last_sync: Utc::now(),
-        is_in_sync: false,
-        pending_operations: 0,
+        primary_connected,
+        backup_connected,
+        last_sync,
+        is_in_sync: primary_connected && backup_connected && pending_operations == 0,
+        pending_operations,
     })
 }
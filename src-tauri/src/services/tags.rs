@@ -1,3 +1,4 @@
+use crate::error::Result;
 use crate::models::tag::Tag;
 use sqlx::SqlitePool;
 
@@ -10,17 +11,17 @@ impl TagService {
         Self { pool }
     }
 
-    pub async fn add_tag(&self, photo_id: i64, tag_name: String) -> Result<(), String> {
+    pub async fn add_tag(&self, photo_id: i64, tag_name: String) -> Result<()> {
         // Logic to add a tag to a photo
         Ok(())
     }
 
-    pub async fn remove_tag(&self, photo_id: i64, tag_id: i64) -> Result<(), String> {
+    pub async fn remove_tag(&self, photo_id: i64, tag_id: i64) -> Result<()> {
         // Logic to remove a tag from a photo
         Ok(())
     }
 
-    pub async fn get_photo_tags(&self, photo_id: i64) -> Result<Vec<Tag>, String> {
+    pub async fn get_photo_tags(&self, photo_id: i64) -> Result<Vec<Tag>> {
         // Logic to get all tags for a photo
         Ok(vec![])
     }
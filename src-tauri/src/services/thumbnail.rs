@@ -0,0 +1,218 @@
+use crate::error::{PhotoVaultError, Result};
+use crate::models::thumbnail::ThumbnailSize;
+use crate::services::format::{self, PhotoFormat};
+use crate::services::metadata;
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+
+const THUMBNAIL_QUALITY: u8 = 85;
+
+/// Generates JPEG thumbnails and caches them under `.photovault/cache` on a
+/// drive. Entries are keyed by file hash, so renaming or moving a photo keeps
+/// its thumbnail valid.
+pub struct ThumbnailService {
+    cache_dir: PathBuf,
+}
+
+impl ThumbnailService {
+    pub fn new(drive_root: &Path) -> Self {
+        Self {
+            cache_dir: drive_root.join(".photovault").join("cache"),
+        }
+    }
+
+    pub fn cache_path(&self, file_hash: &str, size: ThumbnailSize) -> PathBuf {
+        let shard = file_hash.get(..2).unwrap_or("00");
+        self.cache_dir
+            .join(size.name())
+            .join(shard)
+            .join(format!("{}.jpg", file_hash))
+    }
+
+    pub async fn generate_thumbnail(
+        &self,
+        path: &Path,
+        file_hash: &str,
+        size: ThumbnailSize,
+    ) -> Result<Vec<u8>> {
+        let cache_path = self.cache_path(file_hash, size);
+        if let Ok(bytes) = tokio::fs::read(&cache_path).await {
+            return Ok(bytes);
+        }
+
+        let path = path.to_path_buf();
+        let bytes = tokio::task::spawn_blocking(move || render_thumbnail(&path, size))
+            .await??;
+
+        // Write to a temporary file first so a crash never leaves a truncated
+        // thumbnail behind in the cache.
+        if let Some(parent) = cache_path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| PhotoVaultError::io(parent, e))?;
+        }
+        let temp_path = cache_path.with_extension("tmp");
+        tokio::fs::write(&temp_path, &bytes).await.map_err(|e| PhotoVaultError::io(&temp_path, e))?;
+        tokio::fs::rename(&temp_path, &cache_path).await.map_err(|e| PhotoVaultError::io(&cache_path, e))?;
+
+        Ok(bytes)
+    }
+
+    /// Removes every cached thumbnail on this drive.
+    pub async fn clear_cache(&self) -> Result<()> {
+        match tokio::fs::remove_dir_all(&self.cache_dir).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(PhotoVaultError::io(&self.cache_dir, e)),
+        }
+    }
+}
+
+fn render_thumbnail(path: &Path, size: ThumbnailSize) -> Result<Vec<u8>> {
+    let format = format::sniff_file(path)
+        .map_err(|e| PhotoVaultError::io(path, e))?
+        .ok_or_else(|| PhotoVaultError::UnsupportedFormat {
+            message: format!("{} is not a supported image", path.display()),
+        })?;
+    let image = load_image(path, format)?;
+    let orientation = metadata::read_exif(path, format)
+        .and_then(|exif| exif.orientation)
+        .unwrap_or(1);
+
+    let max = size.max_dimension();
+    let thumbnail = apply_orientation(image.thumbnail(max, max), orientation);
+
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, THUMBNAIL_QUALITY)
+        .encode_image(&DynamicImage::ImageRgb8(thumbnail.to_rgb8()))?;
+    Ok(bytes)
+}
+
+/// Decodes an image with the `image` crate where possible. RAW files fall
+/// back to their largest embedded JPEG preview, read from its offset in the
+/// file. HEIC and AVIF are not supported: there is no decoder for them, and
+/// the previews they embed are coded the same way as the image.
+fn load_image(path: &Path, format: PhotoFormat) -> Result<DynamicImage> {
+    if let Some(image_format) = format.image_format() {
+        let mut reader = image::io::Reader::open(path).map_err(|e| PhotoVaultError::io(path, e))?;
+        reader.set_format(image_format);
+        return Ok(reader.decode()?);
+    }
+    if format.is_heif() {
+        return Err(PhotoVaultError::UnsupportedFormat {
+            message: format!("Cannot decode {} images", format.name()),
+        });
+    }
+
+    let (offset, _) = format::find_embedded_preview(path)?.ok_or_else(|| PhotoVaultError::UnsupportedFormat {
+        message: format!("No embedded preview found in {} file", format.name()),
+    })?;
+    format::decode_jpeg_at(path, offset)
+}
+
+/// Number of catalogued photos in a format `load_image` cannot decode, so
+/// they have no thumbnail.
+pub async fn count_unsupported(pool: &SqlitePool) -> Result<u64> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM photos WHERE format IN (?, ?)")
+        .bind(PhotoFormat::Heic.name())
+        .bind(PhotoFormat::Avif.name())
+        .fetch_one(pool)
+        .await?;
+    Ok(count as u64)
+}
+
+/// Rotates and flips an image according to its EXIF orientation (1-8).
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgb, RgbImage};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_apply_orientation_turns_the_top_left_corner_upright() {
+        // A 3x2 image with a red top-left pixel.
+        let mut image = RgbImage::new(3, 2);
+        image.put_pixel(0, 0, Rgb([255, 0, 0]));
+        let image = DynamicImage::ImageRgb8(image);
+        let red_at = |orientation| {
+            let oriented = apply_orientation(image.clone(), orientation);
+            let (width, height) = oriented.dimensions();
+            let pixels = oriented.to_rgb8();
+            let (x, y, _) = pixels.enumerate_pixels().find(|(_, _, pixel)| pixel[0] == 255).unwrap();
+            ((width, height), (x, y))
+        };
+        assert_eq!(red_at(1), ((3, 2), (0, 0)));
+        assert_eq!(red_at(2), ((3, 2), (2, 0)));
+        assert_eq!(red_at(3), ((3, 2), (2, 1)));
+        assert_eq!(red_at(4), ((3, 2), (0, 1)));
+        assert_eq!(red_at(5), ((2, 3), (0, 0)));
+        assert_eq!(red_at(6), ((2, 3), (1, 0)));
+        assert_eq!(red_at(7), ((2, 3), (1, 2)));
+        assert_eq!(red_at(8), ((2, 3), (0, 2)));
+    }
+
+    #[tokio::test]
+    async fn test_generate_thumbnail_scales_down_and_caches_by_hash() {
+        let drive = tempdir().unwrap();
+        let path = drive.path().join("wide.png");
+        RgbImage::from_pixel(1200, 600, Rgb([10, 120, 200])).save(&path).unwrap();
+
+        let service = ThumbnailService::new(drive.path());
+        let bytes = service.generate_thumbnail(&path, "abcdef", ThumbnailSize::Grid).await.unwrap();
+        let thumbnail = image::load_from_memory(&bytes).unwrap();
+        let max = ThumbnailSize::Grid.max_dimension();
+        assert_eq!(thumbnail.dimensions(), (max, max / 2));
+        assert!(service.cache_path("abcdef", ThumbnailSize::Grid).exists());
+
+        // Served from the cache even once the photo is gone.
+        std::fs::remove_file(&path).unwrap();
+        let cached = service.generate_thumbnail(&path, "abcdef", ThumbnailSize::Grid).await.unwrap();
+        assert_eq!(cached, bytes);
+
+        service.clear_cache().await.unwrap();
+        assert!(!service.cache_path("abcdef", ThumbnailSize::Grid).exists());
+    }
+
+    #[test]
+    fn test_heic_is_reported_as_unsupported() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("photo.heic");
+        let mut data = 24u32.to_be_bytes().to_vec();
+        data.extend_from_slice(b"ftypheic\0\0\0\0mif1heic");
+        std::fs::write(&path, data).unwrap();
+
+        let result = render_thumbnail(&path, ThumbnailSize::Grid);
+        assert!(matches!(result, Err(PhotoVaultError::UnsupportedFormat { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_count_unsupported_counts_heif_photos() {
+        let db_dir = tempdir().unwrap();
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = crate::db::init_db(&db_dir.path().join("test.db"), &migrations).await.unwrap();
+        for (name, format) in [("a.heic", "HEIC"), ("b.avif", "AVIF"), ("c.jpg", "JPEG")] {
+            sqlx::query("INSERT INTO photos (path, filename, file_hash, format) VALUES (?, ?, ?, ?)")
+                .bind(name)
+                .bind(name)
+                .bind(name)
+                .bind(format)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        assert_eq!(count_unsupported(&pool).await.unwrap(), 2);
+    }
+}
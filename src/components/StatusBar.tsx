@@ -1,17 +1,48 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { SyncQueue } from './SyncQueue';
 
+interface SyncStatus {
+  backup_connected: boolean;
+}
+
+interface BackupConnectionEvent {
+  connected: boolean;
+  problem: string | null;
+}
+
 export function StatusBar() {
-  const isBackupConnected = false;
+  const [isBackupConnected, setIsBackupConnected] = useState(false);
+  const [backupProblem, setBackupProblem] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<SyncStatus>('verify_sync_status')
+      .then((status) => setIsBackupConnected(status.backup_connected))
+      .catch(console.error);
+
+    const unlisten = listen<BackupConnectionEvent>('backup-connection-changed', (event) => {
+      setIsBackupConnected(event.payload.connected);
+      setBackupProblem(event.payload.problem);
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
 
   return (
     <footer className="p-2 border-t flex justify-between items-center">
       <div>
         <span>Backup Status: </span>
-        <span className={isBackupConnected ? 'text-green-500' : 'text-red-500'}>
-          {isBackupConnected ? 'Connected' : 'Disconnected'}
+        <span
+          className={isBackupConnected ? 'text-green-500' : 'text-red-500'}
+          title={backupProblem ?? undefined}
+        >
+          {isBackupConnected ? 'Connected' : backupProblem ? 'Not recognized' : 'Disconnected'}
         </span>
       </div>
       <SyncQueue />
     </footer>
   );
-}
\ No newline at end of file
+}